@@ -24,20 +24,35 @@ case "$CURRENT_SHELL" in
 complete -c walrs -e
 
 # Basic flags
-complete -c walrs -s i -l image -d "path to image or directory" -r -F
-complete -c walrs -s r -l reload -d "reload without changing the wallpaper"
-complete -c walrs -s R -l reload-no -d "will be removed in the next update; use -w instead"  
-complete -c walrs -s t -l theme -d "use external theme file from .config/walrs/colorschemes" -r -F
-complete -c walrs -s g -l generate -d "generate & save theme to .config/walrs/colorschemes" -x
+complete -c walrs -s i -l image -d "deprecated, use generate -i" -r -F
+complete -c walrs -s r -l reload -d "deprecated, use reload"
+complete -c walrs -s R -l reload-no -d "deprecated, use -W reload"
+complete -c walrs -s t -l theme -d "deprecated, use theme apply" -r -F
+complete -c walrs -l filter -d "deprecated, use theme apply --filter" -x
+complete -c walrs -s g -l generate -d "deprecated, use theme save" -x
 complete -c walrs -s s -l saturation -d "set saturation value (-128 to 127)" -x
 complete -c walrs -s b -l brightness -d "set brightness value (-128 to 127)" -x
 complete -c walrs -s S -l scripts -d "skip running scripts in ~/.config/walrs/scripts/"
 complete -c walrs -s W -l walless -d "skip changing the wallpaper"
+complete -c walrs -s e -l extended -d "also generate colors 16-255 from the palette"
+complete -c walrs -l order -d "how to pick from a wallpaper folder" -x -a "random name mtime next previous"
+complete -c walrs -l recursive -d "also pick from subfolders, following symlinks"
+complete -c walrs -l tty -d "only recolor this terminal" -x -a "(ls /dev/pts | string match -r '^[0-9]+\$')"
+complete -c walrs -s A -l appearance -d "use the light or dark variant matching the system preference"
 complete -c walrs -s q -l quiet -d "set quit mode (no output)"
 complete -c walrs -s v -l version -d "show version"
 complete -c walrs -l help -d "display help"
 complete -c walrs -a help -d "display help"
 
+# Commands
+complete -c walrs -n "__fish_use_subcommand" -a "generate" -d "generate a colorscheme from an image and apply it"
+complete -c walrs -n "__fish_use_subcommand" -a "theme" -d "manage colorschemes"
+complete -c walrs -n "__fish_use_subcommand" -a "reload" -d "apply the current colors and wallpaper again"
+complete -c walrs -n "__fish_use_subcommand" -a "template" -d "render templates again from the current colors"
+complete -c walrs -n "__fish_use_subcommand" -a "wallpaper" -d "set the wallpaper without changing colors"
+complete -c walrs -n "__fish_use_subcommand" -a "follow schedule undo history save load daemon ctl watch"
+complete -c walrs -n "__fish_seen_subcommand_from theme" -a "list preview import validate match apply export save"
+
 # Saturation and brightness numeric completion
 for i in (seq -128 127)
     complete -c walrs -s s -l saturation -x -a "$i"
@@ -63,18 +78,26 @@ _walrs() {
     typeset -A opt_args
 
     _arguments \
-        '(-i --image)'{-i,--image}'[path to image or directory]:image file:_files -g "*.{jpg,jpeg,png,bmp,gif,tiff,webp}(-.)"' \
-        '(-r --reload)'{-r,--reload}'[reload without changing the wallpaper]' \
-        '(-R --reload-no)'{-R,--reload-no}'[will be removed in the next update; use -w instead]' \
-        '(-t --theme)'{-t,--theme}'[use external theme file from .config/walrs/colorschemes]:theme file:_files -g "*.json(-.)"' \
-        '(-g --generate)'{-g,--generate}'[generate & save theme to .config/walrs/colorschemes]:theme name:' \
+        '(-i --image)'{-i,--image}'[deprecated, use generate -i]:image file:_files -g "*.{jpg,jpeg,png,bmp,gif,tiff,webp}(-.)"' \
+        '(-r --reload)'{-r,--reload}'[deprecated, use reload]' \
+        '(-R --reload-no)'{-R,--reload-no}'[deprecated, use -W reload]' \
+        '(-t --theme)'{-t,--theme}'[deprecated, use theme apply]:theme file:_files -g "*.json(-.)"' \
+        '--filter[deprecated, use theme apply --filter]:glob:' \
+        '(-g --generate)'{-g,--generate}'[deprecated, use theme save]:theme name:' \
         '(-s --saturation)'{-s,--saturation}'[set saturation value (-128 to 127)]:saturation:({-128..127})' \
         '(-b --brightness)'{-b,--brightness}'[set brightness value (-128 to 127)]:brightness:({-128..127})' \
         '(-S --scripts)'{-S,--scripts}'[skip running scripts in ~/.config/walrs/scripts/]' \
         '(-W --walless)'{-W,--walless}'[skip changing the wallpaper]' \
+        '(-e --extended)'{-e,--extended}'[also generate colors 16-255 from the palette]' \
+        '(-A --appearance)'{-A,--appearance}'[use the light or dark variant matching the system preference]' \
+        '--order[how to pick from a wallpaper folder]:order:(random name mtime next previous)' \
+        '--recursive[also pick from subfolders, following symlinks]' \
+        '*--tty[only recolor this terminal]:terminal:_files -W /dev/pts' \
         '(-q --quiet)'{-q,--quiet}'[set quit mode (no output)]' \
         '(-v --version)'{-v,--version}'[show version]' \
-        '(--help)--help[display usage information]'
+        '(--help)--help[display usage information]' \
+        '1:command:(generate theme reload template wallpaper follow schedule undo history save load daemon ctl watch)' \
+        '*::arguments:_files'
 }
 
 _walrs "$@"
@@ -113,7 +136,13 @@ _walrs_completion() {
     cur="${COMP_WORDS[COMP_CWORD]}"
     prev="${COMP_WORDS[COMP_CWORD-1]}"
 
-    opts="-i --image -r --reload -R --reload-no -t --theme -g --generate -s --saturation -b --brightness -S --scripts -W --walless -q --quiet -v --version --help help"
+    opts="-i --image -r --reload -R --reload-no -t --theme --filter -g --generate -s --saturation -b --brightness -S --scripts -W --walless -e --extended -A --appearance --order --recursive --tty -q --quiet -v --version --help help"
+    opts+=" generate theme reload template wallpaper follow schedule undo history save load daemon ctl watch"
+
+    if [[ ${prev} == theme ]]; then
+        COMPREPLY=( $(compgen -W "list preview import validate match apply export save" -- "${cur}") )
+        return 0
+    fi
 
     case ${prev} in
         -i|--image)
@@ -125,6 +154,14 @@ _walrs_completion() {
             COMPREPLY=( $(compgen -f -X '!*.json' -- "${cur}") )
             return 0
             ;;
+        --order)
+            COMPREPLY=( $(compgen -W "random name mtime next previous" -- "${cur}") )
+            return 0
+            ;;
+        --tty)
+            COMPREPLY=( $(compgen -W "$(ls /dev/pts | grep -E '^[0-9]+$')" -- "${cur}") )
+            return 0
+            ;;
         -g|--generate)
             COMPREPLY=( $(compgen -W "" -- "${cur}") )
             return 0
@@ -0,0 +1,178 @@
+use crate::create_templates::create_template;
+use crate::get_colors::{Palette, get_colors, light_variant};
+use crate::history::{Entry, record};
+use crate::reload::reload;
+use crate::theme::{
+    Theme, apply_theme, install_themes, is_random, load_theme, set_current_theme_name,
+};
+use crate::utils::{command_output, info, warning};
+use std::path::Path;
+use std::process::exit;
+use std::thread::sleep;
+use std::time::Duration;
+
+// dark from 19:00 until 07:00 when no desktop preference is set
+const DAY: std::ops::Range<u32> = 7..19;
+
+// org.freedesktop.appearance color-scheme: 1 prefers dark, 2 prefers light
+// and 0 means no preference
+fn portal_preference() -> Option<bool> {
+    let reply = ["ReadOne", "Read"].iter().find_map(|method| {
+        command_output(
+            "gdbus",
+            &[
+                "call",
+                "--session",
+                "--dest",
+                "org.freedesktop.portal.Desktop",
+                "--object-path",
+                "/org/freedesktop/portal/desktop",
+                "--method",
+                &format!("org.freedesktop.portal.Settings.{method}"),
+                "org.freedesktop.appearance",
+                "color-scheme",
+            ],
+        )
+    })?;
+
+    match reply.split("uint32").nth(1)?.trim_start().chars().next()? {
+        '1' => Some(true),
+        '2' => Some(false),
+        _ => None,
+    }
+}
+
+// older GNOME without the portal
+fn gsettings_preference() -> Option<bool> {
+    let value = command_output(
+        "gsettings",
+        &["get", "org.gnome.desktop.interface", "color-scheme"],
+    )?;
+    match value.trim().trim_matches('\'') {
+        "prefer-dark" => Some(true),
+        "prefer-light" => Some(false),
+        _ => None,
+    }
+}
+
+fn night() -> bool {
+    command_output("date", &["+%H"])
+        .and_then(|h| h.trim().parse::<u32>().ok())
+        .is_some_and(|hour| !DAY.contains(&hour))
+}
+
+pub fn prefers_dark() -> bool {
+    portal_preference()
+        .or_else(gsettings_preference)
+        .unwrap_or_else(night)
+}
+
+// `dark:light` pairs resolve to one side, anything else is returned as is
+pub fn pick_pair(theme_name: &str) -> String {
+    match theme_name.split_once(':') {
+        Some((dark, _)) if prefers_dark() => dark.to_string(),
+        Some((_, light)) => light.to_string(),
+        None => theme_name.to_string(),
+    }
+}
+
+// generate from the wallpaper, using the light variant when preferred;
+// `adjust` is the brightness and saturation the palette was extracted with
+pub fn apply_image(
+    palette: &Palette,
+    image_path: &str,
+    dark: bool,
+    adjust: (Option<i16>, Option<i16>),
+    extended: bool,
+    send: bool,
+) {
+    let colors = if dark {
+        palette.0.clone()
+    } else {
+        light_variant(&palette.0)
+    };
+    let special = [colors[0], colors[7], colors[7]];
+
+    let name = Path::new(image_path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("wallpaper");
+    let mut theme = Theme::new(name, colors.clone());
+    theme.special = special;
+    let mut entry = Entry::new(&theme, image_path, palette.1, extended);
+    (entry.brightness, entry.saturation) = adjust;
+    entry.seed = palette.2;
+    record(&entry, send);
+
+    create_template(
+        (colors, palette.1),
+        special,
+        image_path,
+        palette.2,
+        extended,
+        send,
+    );
+    set_current_theme_name(None, send);
+}
+
+// re-apply the theme pair or wallpaper every time the preference flips
+pub fn follow(
+    theme: Option<String>,
+    image: Option<String>,
+    interval: u64,
+    scripts: bool,
+    extended: bool,
+    ttys: &[u32],
+    send: bool,
+) {
+    if theme.is_none() && image.is_none() {
+        warning(
+            "Follow",
+            "pass a theme pair with -t or a wallpaper with -i",
+            send,
+        );
+        exit(1)
+    }
+    // a typo in either side of the pair should fail now, not at the next flip
+    if let Some(pair) = &theme {
+        install_themes(send);
+        let (dark, light) = pair.split_once(':').unwrap_or((pair, pair));
+        for name in [dark, light].into_iter().filter(|n| !is_random(n)) {
+            if let Err(e) = load_theme(name, None, send) {
+                warning("Follow", &e, send);
+                exit(1)
+            }
+        }
+    }
+    let palette = image.as_ref().map(|i| get_colors(i, send, None, None));
+    let mut current: Option<bool> = None;
+
+    loop {
+        let dark = prefers_dark();
+        if current != Some(dark) {
+            info(
+                "Appearance",
+                if dark {
+                    "switching to dark"
+                } else {
+                    "switching to light"
+                },
+                send,
+            );
+            match (&theme, &palette, &image) {
+                (Some(pair), _, _) => match load_theme(&pick_pair(pair), None, send) {
+                    Ok(theme) => apply_theme(theme, scripts, extended, ttys, send),
+                    Err(e) => warning("Follow", &e, send),
+                },
+                (None, Some(palette), Some(image)) => {
+                    apply_image(palette, image, dark, (None, None), extended, send);
+                    // the wallpaper only needs to be set once
+                    reload(send, current.is_some(), scripts, ttys);
+                }
+                _ => unreachable!(),
+            }
+            current = Some(dark);
+        }
+        sleep(Duration::from_secs(interval.max(1)));
+    }
+}
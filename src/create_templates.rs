@@ -1,122 +1,250 @@
-use crate::utils::{get_cache, get_config, share_files, warning};
+use crate::get_colors::{extended_palette, semantic_roles};
+use crate::history::current;
+use crate::tonal::TonalPalettes;
+use crate::utils::{get_cache, get_config, info, share_files, warning, write_atomic};
 use std::{
     fs::{self, create_dir_all, read_to_string, write},
     process::exit,
+    thread,
 };
 
-fn change(template: &str, colors: (u8, u8, u8), alpha: u8) -> String {
+fn change(modifier: Option<&str>, colors: (u8, u8, u8), alpha: u8) -> Option<String> {
     let (r, g, b) = colors;
 
-    if template.contains(".strip") {
-        format!("{r:02x}{g:02x}{b:02x}")
-    } else if template.contains(".xrgba") {
-        format!("{r:02x}/{g:02x}/{b:02x}/{alpha:02x}")
-    } else if template.contains(".rgba") {
-        format!("{r},{g},{b},{alpha}")
-    } else if template.contains(".rgb") {
-        format!("{r},{g},{b}")
-    } else if template.contains(".alpha_per") {
-        format!("{:.1}", (alpha / 255) * 100)
-    } else if template.contains(".alpha") {
-        format!("#{r:02x}{g:02x}{b:02x}{alpha:02x}")
-    } else {
-        format!("#{r:02x}{g:02x}{b:02x}")
-    }
+    Some(match modifier {
+        None => format!("#{r:02x}{g:02x}{b:02x}"),
+        Some("strip") => format!("{r:02x}{g:02x}{b:02x}"),
+        Some("xrgba") => format!("{r:02x}/{g:02x}/{b:02x}/{alpha:02x}"),
+        Some("rgba") => format!("{r},{g},{b},{alpha}"),
+        Some("rgb") => format!("{r},{g},{b}"),
+        Some("alpha") | Some("alpha_dec") => format!("#{r:02x}{g:02x}{b:02x}{alpha:02x}"),
+        Some(_) => return None,
+    })
 }
 
-fn fill_template(
-    template_name: &str,
-    template: &str,
-    colors: &(Vec<(u8, u8, u8)>, u8),
-    wallpaper: &str,
-    send: bool,
-) {
-    let output_path = get_cache(send).join("wal").join(template_name);
-    let alpha = colors.1;
-
-    let mut result = template
-        .replace("{wallpaper}", wallpaper)
-        .replace("{alpha}", &format!("{}", (alpha / 255) * 100));
-
-    if !colors.0.is_empty() {
-        let background_patterns = [
-            "{background.strip}",
-            "{background.xrgba}",
-            "{background.rgba}",
-            "{background.rgb}",
-            "{background.alpha}",
-            "{background}",
-        ];
-
-        for pattern in background_patterns {
-            let replacement = change(pattern, colors.0[0], alpha);
-            result = result.replace(pattern, &replacement);
+// everything a template can reference
+struct Scheme<'a> {
+    colors: &'a (Vec<(u8, u8, u8)>, u8),
+    special: [(u8, u8, u8); 3],
+    roles: Vec<(&'static str, (u8, u8, u8))>,
+    extended: Vec<(u8, u8, u8)>,
+    tonal: TonalPalettes,
+    wallpaper: &'a str,
+}
+
+// resolve a single placeholder name (without braces), None keeps it verbatim
+fn resolve(name: &str, scheme: &Scheme) -> Option<String> {
+    let (palette, alpha) = (&scheme.colors.0, scheme.colors.1);
+
+    match name {
+        "wallpaper" => return Some(scheme.wallpaper.to_string()),
+        "alpha" => return Some(format!("{}", (alpha / 255) * 100)),
+        "checksum" => {
+            return Some(
+                palette
+                    .iter()
+                    .map(|(r, g, b)| format!("{r:02X}{g:02X}{b:02X}"))
+                    .collect(),
+            );
         }
+        _ => {}
     }
 
-    if colors.0.len() > 7 {
-        let foreground_patterns = [
-            "{foreground.strip}",
-            "{foreground.xrgba}",
-            "{foreground.rgba}",
-            "{foreground.rgb}",
-            "{foreground.alpha}",
-            "{foreground}",
-        ];
-
-        for pattern in foreground_patterns {
-            let replacement = change(pattern, colors.0[7], alpha);
-            result = result.replace(pattern, &replacement);
-        }
+    let (base, modifier) = match name.split_once('.') {
+        Some((base, modifier)) => (base, Some(modifier)),
+        None => (name, None),
+    };
+
+    // tonal palettes take the tone first: {primary.40} or {primary.40.strip}
+    if let Some(palette) = scheme.tonal.get(base) {
+        let (tone, modifier) = match modifier?.split_once('.') {
+            Some((tone, modifier)) => (tone, Some(modifier)),
+            None => (modifier?, None),
+        };
+        let tone: u8 = tone.parse().ok().filter(|t| *t <= 100)?;
+        return change(modifier, palette.tone(tone), alpha);
+    }
 
-        let cursor_patterns = [
-            "{cursor.strip}",
-            "{cursor.xrgba}",
-            "{cursor.rgba}",
-            "{cursor.rgb}",
-            "{cursor.alpha}",
-            "{cursor}",
-        ];
-
-        for pattern in cursor_patterns {
-            let replacement = change(pattern, colors.0[7], alpha);
-            result = result.replace(pattern, &replacement);
+    let color = match base {
+        "background" => scheme.special[0],
+        "foreground" => scheme.special[1],
+        "cursor" => scheme.special[2],
+        _ if !base.starts_with("color") => scheme.roles.iter().find(|(role, _)| *role == base)?.1,
+        _ => {
+            let i: usize = base.strip_prefix("color")?.parse().ok()?;
+            if i >= 16 {
+                *scheme.extended.get(i - 16)?
+            } else {
+                *palette.get(i)?
+            }
         }
+    };
+
+    if modifier == Some("alpha_dec") && !base.starts_with("color") {
+        return None;
     }
+    change(modifier, color, alpha)
+}
+
+// scan the template once and substitute every known placeholder
+fn render(template: &str, scheme: &Scheme) -> String {
+    let mut result = String::with_capacity(template.len() + template.len() / 4);
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        result.push_str(&rest[..open]);
+        rest = &rest[open..];
 
-    for i in 0..colors.0.len().min(16) {
-        let color = colors.0[i];
-
-        let patterns = [
-            format!("{{color{i}.strip}}"),
-            format!("{{color{i}.xrgba}}"),
-            format!("{{color{i}.rgba}}"),
-            format!("{{color{i}.rgb}}"),
-            format!("{{color{i}.alpha_dec}}"),
-            format!("{{color{i}.alpha}}"),
-            format!("{{color{i}}}"),
-        ];
-
-        for pattern in patterns {
-            let replacement = change(&pattern, color, alpha);
-            result = result.replace(&pattern, &replacement);
+        let name = rest[1..]
+            .find('}')
+            .map(|close| &rest[1..close + 1])
+            .filter(|n| {
+                !n.is_empty()
+                    && n.len() <= 32
+                    && n.bytes()
+                        .all(|c| c.is_ascii_alphanumeric() || c == b'.' || c == b'_')
+            });
+
+        match name.and_then(|n| resolve(n, scheme).map(|v| (n, v))) {
+            Some((n, value)) => {
+                result.push_str(&value);
+                rest = &rest[n.len() + 2..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
         }
     }
+    result.push_str(rest);
 
-    if result.contains("{checksum}") {
-        let checksum = colors
-            .0
-            .iter()
-            .map(|(r, g, b)| format!("{r:02X}{g:02X}{b:02X}"))
-            .collect::<Vec<String>>()
-            .join("");
-        result = result.replace("{checksum}", &checksum);
+    result
+}
+
+fn fill_template(template_name: &str, template: &str, scheme: &Scheme, send: bool) -> bool {
+    let output_path = get_cache(send).join("wal").join(template_name);
+    let result = render(template, scheme);
+
+    write_atomic(&output_path, &result).unwrap_or_else(|_| {
+        warning("Template", &format!("can't write {template_name}"), send);
+        false
+    })
+}
+
+// render all templates, split across the available cores, and return the
+// names of the outputs that actually changed
+fn fill_templates(templates: &[(String, String)], scheme: &Scheme, send: bool) -> Vec<String> {
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = templates.len().div_ceil(workers).max(1);
+
+    let mut changed: Vec<String> = thread::scope(|s| {
+        let handles: Vec<_> = templates
+            .chunks(chunk)
+            .map(|part| {
+                s.spawn(move || {
+                    part.iter()
+                        .filter(|(name, content)| fill_template(name, content, scheme, send))
+                        .map(|(name, _)| name.clone())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap_or_default())
+            .collect()
+    });
+    changed.sort();
+
+    changed
+}
+
+fn build_scheme<'a>(
+    colors: &'a (Vec<(u8, u8, u8)>, u8),
+    special: [(u8, u8, u8); 3],
+    wallpaper: &'a str,
+    seed: Option<(u8, u8, u8)>,
+    extended: bool,
+) -> Scheme<'a> {
+    let roles = semantic_roles(&colors.0);
+    let seed = seed
+        .or_else(|| roles.first().map(|(_, c)| *c))
+        .unwrap_or((0x80, 0x80, 0x80));
+
+    Scheme {
+        tonal: TonalPalettes::new(seed),
+        extended: if extended {
+            extended_palette(&colors.0)
+        } else {
+            Vec::new()
+        },
+        roles,
+        special,
+        colors,
+        wallpaper,
     }
+}
+
+// re-render a single user template with the scheme applied right now,
+// returns whether its output changed
+pub fn render_template(name: &str, send: bool) -> bool {
+    let path = get_config(send).join("walrs").join("templates").join(name);
+    let (Ok(template), Some(entry)) = (read_to_string(path), current(send)) else {
+        return false;
+    };
+    let colors = (entry.theme.colors, entry.alpha);
+    let scheme = build_scheme(
+        &colors,
+        entry.theme.special,
+        &entry.wallpaper,
+        entry.seed,
+        entry.extended,
+    );
+
+    fill_template(name, &template, &scheme, send)
+}
+
+// render every template again, or only the named one, from the current
+// scheme without reloading anything
+pub fn render_templates(name: Option<String>, send: bool) {
+    let Some(entry) = current(send) else {
+        warning("Template", "nothing has been applied yet", send);
+        exit(1)
+    };
 
-    write(output_path, result).expect("Failed to write filled template");
+    match name {
+        Some(name) => {
+            let path = get_config(send).join("walrs").join("templates").join(&name);
+            if !path.is_file() {
+                warning("Template", &format!("can't find {name}"), send);
+                exit(1)
+            }
+            render_template(&name, send);
+            info("Template", &format!("rendered {name}"), send);
+        }
+        None => {
+            create_template(
+                (entry.theme.colors, entry.alpha),
+                entry.theme.special,
+                &entry.wallpaper,
+                entry.seed,
+                entry.extended,
+                send,
+            );
+            info("Template", "create templates", send);
+        }
+    }
 }
 
-pub fn create_template(colors: (Vec<(u8, u8, u8)>, u8), wallpaper: &str, send: bool) {
+pub fn create_template(
+    colors: (Vec<(u8, u8, u8)>, u8),
+    special: [(u8, u8, u8); 3],
+    wallpaper: &str,
+    seed: Option<(u8, u8, u8)>,
+    extended: bool,
+    send: bool,
+) {
     let system_template_path = share_files().join("templates");
     let user_template_path = get_config(send).join("walrs").join("templates");
     let cache_path = get_cache(send).join("wal");
@@ -127,6 +255,7 @@ pub fn create_template(colors: (Vec<(u8, u8, u8)>, u8), wallpaper: &str, send: b
 
     // Check if user templates directory exists and has templates
     let mut has_user_templates = false;
+    let mut templates = Vec::new();
     if let Ok(entries) = fs::read_dir(&user_template_path) {
         for entry in entries.flatten() {
             if entry.path().is_file() {
@@ -137,7 +266,7 @@ pub fn create_template(colors: (Vec<(u8, u8, u8)>, u8), wallpaper: &str, send: b
                 let Some(name) = entry.file_name().into_string().ok() else {
                     continue;
                 };
-                fill_template(&name, &content, &colors, wallpaper, send);
+                templates.push((name, content));
             }
         }
     }
@@ -163,9 +292,82 @@ pub fn create_template(colors: (Vec<(u8, u8, u8)>, u8), wallpaper: &str, send: b
                     let user_file_path = format!("{}/{name}", user_template_path.display());
                     let _ = write(&user_file_path, &content);
 
-                    fill_template(&name, &content, &colors, wallpaper, send);
+                    templates.push((name, content));
                 }
             }
         }
     }
+
+    let scheme = build_scheme(&colors, special, wallpaper, seed, extended);
+    let mut changed = fill_templates(&templates, &scheme, send);
+
+    // colors 16-255, one per line, picked up by reload when present
+    let extended_path = cache_path.join("colors-256");
+    if scheme.extended.is_empty() {
+        let _ = fs::remove_file(extended_path);
+    } else {
+        let content: String = scheme
+            .extended
+            .iter()
+            .map(|(r, g, b)| format!("#{r:02x}{g:02x}{b:02x}\n"))
+            .collect();
+        if write_atomic(&extended_path, &content).unwrap_or(false) {
+            changed.push("colors-256".to_string());
+        }
+    }
+
+    if changed.is_empty() {
+        info("Template", "all outputs are up to date", send);
+    } else {
+        info(
+            "Template",
+            &format!(
+                "updated {} output(s): {}",
+                changed.len(),
+                changed.join(", ")
+            ),
+            send,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_scheme, render};
+
+    #[test]
+    fn renders_placeholders() {
+        let colors = ((0..16).map(|i| (i * 16, i * 16, i * 16)).collect(), 100);
+        let special = [(1, 2, 3), (4, 5, 6), (7, 8, 9)];
+        let scheme = build_scheme(&colors, special, "/w.png", None, false);
+
+        assert_eq!(
+            render("{color1} {color1.strip} {color1.rgb}", &scheme),
+            "#101010 101010 16,16,16"
+        );
+        assert_eq!(
+            render("bg={background} {wallpaper}", &scheme),
+            "bg=#010203 /w.png"
+        );
+        assert!(render("{primary.40}", &scheme).starts_with('#'));
+    }
+
+    #[test]
+    fn keeps_unknown_placeholders() {
+        let colors = (vec![(0, 0, 0); 16], 100);
+        let scheme = build_scheme(&colors, [(0, 0, 0); 3], "None", None, false);
+
+        for text in [
+            "{foo}",
+            "{color99}",
+            "{color1.bogus}",
+            "{ {",
+            "a { b",
+            "{}",
+            "{color1",
+        ] {
+            assert_eq!(render(text, &scheme), text);
+        }
+        assert_eq!(render("{{color0}}", &scheme), "{#000000}");
+    }
 }
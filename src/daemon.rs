@@ -0,0 +1,340 @@
+use crate::appearance::apply_image;
+use crate::get_colors::{Palette, extract_colors};
+use crate::reload::reload;
+use crate::theme::parse_hex;
+use crate::utils::{
+    find_image, get_absolute_path, get_cache, image_path, info, remember_position, warning,
+    write_atomic,
+};
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs::{create_dir_all, metadata, read_to_string, remove_file};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::mpsc::{RecvTimeoutError, Sender, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// a control command and where to send the answer
+type Request = (String, Sender<String>);
+
+// how many shown images `prev` can step back through
+const MAX_SHOWN: usize = 50;
+
+// removes the socket when the daemon stops
+struct Socket(PathBuf);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.0);
+    }
+}
+
+fn socket_path(send: bool) -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| get_cache(send).join("walrs"))
+        .join("walrs.sock")
+}
+
+// "30m", "1h", "45s" or plain seconds
+pub fn parse_interval(interval: &str) -> Option<Duration> {
+    let interval = interval.trim();
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(interval.len());
+    let (number, unit) = interval.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let seconds = match unit {
+        "" | "s" => number,
+        "m" => number * 60,
+        "h" => number * 3600,
+        _ => return None,
+    };
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+// extracted palettes keyed by path, size and mtime so a slideshow over the
+// same folder only runs the extraction once per image
+fn cached_colors(image: &str, send: bool) -> Result<Palette, String> {
+    let mut hasher = DefaultHasher::new();
+    image.hash(&mut hasher);
+    if let Ok(meta) = metadata(image) {
+        meta.len().hash(&mut hasher);
+        meta.modified().ok().hash(&mut hasher);
+    }
+    let dir = get_cache(send).join("walrs").join("palettes");
+    let path = dir.join(format!("{:016x}", hasher.finish()));
+
+    if let Ok(content) = read_to_string(&path) {
+        let mut lines = content.lines();
+        let alpha = lines.next().and_then(|a| a.parse().ok());
+        let seed = lines.next().map(parse_hex);
+        let colors: Vec<(u8, u8, u8)> = lines.filter_map(parse_hex).collect();
+        if let (Some(alpha), Some(seed)) = (alpha, seed)
+            && colors.len() == 16
+        {
+            return Ok((colors, alpha, seed));
+        }
+    }
+
+    let palette = extract_colors(image, None, None)?;
+    let mut content = format!("{}\n", palette.1);
+    match palette.2 {
+        Some((r, g, b)) => content += &format!("#{r:02x}{g:02x}{b:02x}\n"),
+        None => content += "none\n",
+    }
+    for (r, g, b) in &palette.0 {
+        content += &format!("#{r:02x}{g:02x}{b:02x}\n");
+    }
+    if create_dir_all(&dir).is_ok() {
+        let _ = write_atomic(&path, &content);
+    }
+
+    Ok(palette)
+}
+
+// what the daemon cycles through and how each image is applied
+pub struct Slideshow<'a> {
+    pub dir: &'a str,
+    pub interval: &'a str,
+    pub order: Option<&'a str>,
+    pub recursive: bool,
+    pub walless: bool,
+    pub scripts: bool,
+    pub extended: bool,
+    pub ttys: &'a [u32],
+}
+
+fn show(image: &str, dir: &str, slideshow: &Slideshow, send: bool) -> Result<(), String> {
+    // so stepping through the folder continues from what is on screen, or
+    // past an image that can't be read
+    remember_position(Path::new(dir), image, send);
+    let palette = cached_colors(image, send)?;
+    info("Daemon", &format!("showing {image}"), send);
+    apply_image(
+        &palette,
+        image,
+        true,
+        (None, None),
+        slideshow.extended,
+        send,
+    );
+    reload(send, slideshow.walless, slideshow.scripts, slideshow.ttys);
+    Ok(())
+}
+
+// show a picked image and keep it for `prev`; a bad pick is skipped so the
+// daemon keeps serving
+fn step(
+    picked: Result<String, String>,
+    dir: &str,
+    slideshow: &Slideshow,
+    shown: &mut Vec<String>,
+    send: bool,
+) -> String {
+    match picked.and_then(|image| show(&image, dir, slideshow, send).map(|_| image)) {
+        Ok(image) => {
+            let answer = format!("showing {image}");
+            shown.push(image);
+            if shown.len() > MAX_SHOWN {
+                shown.remove(0);
+            }
+            answer
+        }
+        Err(e) => {
+            warning("Daemon", &format!("skipping: {e}"), send);
+            format!("skipped: {e}")
+        }
+    }
+}
+
+// accept `walrs ctl` connections and hand their command to the main loop
+fn listen(listener: UnixListener, requests: Sender<Request>) {
+    for stream in listener.incoming().filter_map(|s| s.ok()) {
+        // a client that never finishes its line mustn't block the others
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+        let mut command = String::new();
+        if BufReader::new(&stream).read_line(&mut command).is_err() {
+            continue;
+        }
+        let (reply, answer) = channel();
+        if requests.send((command.trim().to_string(), reply)).is_err() {
+            return;
+        }
+        if let Ok(answer) = answer.recv() {
+            let _ = (&stream).write_all(format!("{answer}\n").as_bytes());
+        }
+        if command.trim() == "stop" {
+            return;
+        }
+    }
+}
+
+pub fn run_daemon(slideshow: Slideshow, send: bool) {
+    let Some(dir) = get_absolute_path(slideshow.dir).filter(|d| Path::new(d).is_dir()) else {
+        warning(
+            "Daemon",
+            &format!("{} is not a folder", slideshow.dir),
+            send,
+        );
+        exit(1)
+    };
+    let Some(interval) = parse_interval(slideshow.interval) else {
+        warning(
+            "Daemon",
+            &format!("invalid interval {}", slideshow.interval),
+            send,
+        );
+        exit(1)
+    };
+    let socket = socket_path(send);
+    if UnixStream::connect(&socket).is_ok() {
+        warning("Daemon", "already running", send);
+        exit(1)
+    }
+    let _ = remove_file(&socket);
+    if let Some(parent) = socket.parent() {
+        let _ = create_dir_all(parent);
+    }
+    let listener = UnixListener::bind(&socket).unwrap_or_else(|e| {
+        warning(
+            "Daemon",
+            &format!("can't listen on {}: {e}", socket.display()),
+            send,
+        );
+        exit(1)
+    });
+    let _socket = Socket(socket);
+    let (requests, commands) = channel();
+    let listening = thread::spawn(move || listen(listener, requests));
+
+    // a bad folder or order stops the daemon right away, later picks only warn
+    let first = image_path(
+        Some(dir.clone()),
+        slideshow.order,
+        slideshow.recursive,
+        send,
+    );
+    // name and mtime only pick where to start, then the slideshow steps on
+    let order = match slideshow.order {
+        Some("name" | "mtime") => Some("next"),
+        order => order,
+    };
+    let next_image = || find_image(Some(dir.clone()), order, slideshow.recursive, send);
+    let mut shown: Vec<String> = Vec::new();
+    let mut paused: Option<Duration> = None;
+    step(Ok(first), &dir, &slideshow, &mut shown, send);
+    let mut next_at = Instant::now() + interval;
+
+    loop {
+        if paused.is_none() && Instant::now() >= next_at {
+            step(next_image(), &dir, &slideshow, &mut shown, send);
+            next_at = Instant::now() + interval;
+        }
+
+        let timeout = match paused {
+            Some(_) => Duration::from_secs(3600),
+            None => next_at.saturating_duration_since(Instant::now()),
+        };
+        let (command, reply) = match commands.recv_timeout(timeout) {
+            Ok(request) => request,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        let answer = match command.as_str() {
+            "next" => {
+                next_at = Instant::now() + interval;
+                step(next_image(), &dir, &slideshow, &mut shown, send)
+            }
+            "prev" => {
+                // step back through what this daemon showed, then the folder
+                shown.pop();
+                let previous = match shown.pop() {
+                    Some(image) => Ok(image),
+                    None => find_image(
+                        Some(dir.clone()),
+                        Some("previous"),
+                        slideshow.recursive,
+                        send,
+                    ),
+                };
+                next_at = Instant::now() + interval;
+                step(previous, &dir, &slideshow, &mut shown, send)
+            }
+            "pause" => {
+                if paused.is_none() {
+                    paused = Some(next_at.saturating_duration_since(Instant::now()));
+                }
+                "paused".to_string()
+            }
+            "resume" => {
+                if let Some(left) = paused.take() {
+                    next_at = Instant::now() + left;
+                }
+                "resumed".to_string()
+            }
+            "status" => {
+                let current = shown.last().map_or("nothing", |s| s.as_str());
+                match paused {
+                    Some(left) => format!("paused on {current}, {}s left", left.as_secs()),
+                    None => format!(
+                        "showing {current}, next in {}s",
+                        next_at.saturating_duration_since(Instant::now()).as_secs()
+                    ),
+                }
+            }
+            "stop" => {
+                let _ = reply.send("stopped".to_string());
+                break;
+            }
+            _ => format!("unknown command {command} (next, prev, pause, resume, status, stop)"),
+        };
+        let _ = reply.send(answer);
+    }
+    // let the listener answer the stop before the process exits
+    let _ = listening.join();
+    info("Daemon", "stopped", send);
+}
+
+// send one command to the running daemon and print its answer
+pub fn control(command: &str, send: bool) {
+    let socket = socket_path(send);
+    let mut stream = UnixStream::connect(&socket).unwrap_or_else(|_| {
+        warning("Ctl", "the daemon isn't running", send);
+        exit(1)
+    });
+    if stream.write_all(format!("{command}\n").as_bytes()).is_err() {
+        warning("Ctl", "can't talk to the daemon", send);
+        exit(1)
+    }
+
+    let mut answer = String::new();
+    let _ = BufReader::new(&stream).read_line(&mut answer);
+    if answer.starts_with("unknown") {
+        warning("Ctl", answer.trim(), send);
+        exit(1)
+    }
+    println!("{}", answer.trim());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_interval;
+    use std::time::Duration;
+
+    #[test]
+    fn parses_intervals() {
+        assert_eq!(parse_interval("45s"), Some(Duration::from_secs(45)));
+        assert_eq!(parse_interval("30m"), Some(Duration::from_secs(1800)));
+        assert_eq!(parse_interval("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_interval(" 90 "), Some(Duration::from_secs(90)));
+        for bad in ["", "0", "5d", "m", "-1m", "1.5h"] {
+            assert_eq!(parse_interval(bad), None, "{bad}");
+        }
+    }
+}
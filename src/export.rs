@@ -0,0 +1,286 @@
+use crate::get_colors::mix;
+use crate::history::current;
+use crate::theme::{Theme, find_theme, read_theme};
+use crate::utils::{info, warning};
+use image::{ImageFormat, Rgb, RgbImage};
+use std::fs::write;
+use std::process::exit;
+
+const ANSI_NAMES: [&str; 8] = [
+    "Black", "Red", "Green", "Yellow", "Blue", "Purple", "Cyan", "White",
+];
+
+fn hex(color: (u8, u8, u8)) -> String {
+    format!("{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+}
+
+// reverse of the base16-shell mapping, the slots with no ANSI counterpart
+// are blended from their neighbours
+fn base16(theme: &Theme) -> String {
+    let c = &theme.colors;
+    let [background, foreground, _] = theme.special;
+    let slots = [
+        background,
+        mix(background, foreground, 0.1),
+        mix(background, foreground, 0.2),
+        c[8],
+        mix(c[8], foreground, 0.5),
+        foreground,
+        mix(foreground, c[15], 0.5),
+        c[15],
+        c[1],
+        mix(c[1], c[3], 0.5),
+        c[3],
+        c[2],
+        c[6],
+        c[4],
+        c[5],
+        mix(c[1], background, 0.4),
+    ];
+
+    let mut out = format!("scheme: \"{}\"\n", theme.name);
+    out += &format!(
+        "author: \"{}\"\n",
+        theme.author.as_deref().unwrap_or("walrs")
+    );
+    for (i, color) in slots.iter().enumerate() {
+        out += &format!("base0{i:X}: \"{}\"\n", hex(*color));
+    }
+
+    out
+}
+
+fn iterm(theme: &Theme) -> String {
+    let entry = |key: &str, (r, g, b): (u8, u8, u8)| {
+        let c = |v: u8| v as f32 / 255.0;
+        format!(
+            "\t<key>{key}</key>\n\t<dict>\n\
+             \t\t<key>Alpha Component</key>\n\t\t<real>1</real>\n\
+             \t\t<key>Blue Component</key>\n\t\t<real>{}</real>\n\
+             \t\t<key>Color Space</key>\n\t\t<string>sRGB</string>\n\
+             \t\t<key>Green Component</key>\n\t\t<real>{}</real>\n\
+             \t\t<key>Red Component</key>\n\t\t<real>{}</real>\n\
+             \t</dict>\n",
+            c(b),
+            c(g),
+            c(r)
+        )
+    };
+    let [background, foreground, cursor] = theme.special;
+
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n<dict>\n",
+    );
+    for (i, color) in theme.colors.iter().enumerate() {
+        out += &entry(&format!("Ansi {i} Color"), *color);
+    }
+    out += &entry("Background Color", background);
+    out += &entry("Foreground Color", foreground);
+    out += &entry("Cursor Color", cursor);
+    out += &entry("Cursor Text Color", background);
+    out += &entry("Selection Color", mix(background, foreground, 0.25));
+    out += &entry("Selected Text Color", foreground);
+    out += "</dict>\n</plist>\n";
+
+    out
+}
+
+fn windows_terminal(theme: &Theme) -> String {
+    let [background, foreground, cursor] = theme.special;
+    let mut fields = vec![
+        ("name".to_string(), theme.name.clone()),
+        ("background".to_string(), format!("#{}", hex(background))),
+        ("foreground".to_string(), format!("#{}", hex(foreground))),
+        ("cursorColor".to_string(), format!("#{}", hex(cursor))),
+        (
+            "selectionBackground".to_string(),
+            format!("#{}", hex(mix(background, foreground, 0.25))),
+        ),
+    ];
+    for (i, color) in theme.colors.iter().enumerate() {
+        let name = ANSI_NAMES[i % 8];
+        let key = if i < 8 {
+            name.to_lowercase()
+        } else {
+            format!("bright{name}")
+        };
+        fields.push((key, format!("#{}", hex(*color))));
+    }
+
+    let body: Vec<String> = fields
+        .iter()
+        .map(|(k, v)| format!("    \"{k}\": \"{}\"", v.replace('"', "\\\"")))
+        .collect();
+    format!("{{\n{}\n}}\n", body.join(",\n"))
+}
+
+fn gimp(theme: &Theme) -> String {
+    let mut out = format!("GIMP Palette\nName: {}\nColumns: 8\n#\n", theme.name);
+    let named = ["background", "foreground", "cursor"]
+        .iter()
+        .zip(theme.special)
+        .map(|(n, c)| (n.to_string(), c));
+    for (name, (r, g, b)) in theme
+        .colors
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (format!("color{i}"), *c))
+        .chain(named)
+    {
+        out += &format!("{r:3} {g:3} {b:3}\t{name}\n");
+    }
+
+    out
+}
+
+// Adobe Swatch Exchange: big-endian blocks of UTF-16 names + RGB floats
+fn ase(theme: &Theme) -> Vec<u8> {
+    let named = ["background", "foreground", "cursor"]
+        .iter()
+        .zip(theme.special)
+        .map(|(n, c)| (n.to_string(), c));
+    let entries: Vec<(String, (u8, u8, u8))> = theme
+        .colors
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (format!("color{i}"), *c))
+        .chain(named)
+        .collect();
+
+    let mut out = b"ASEF".to_vec();
+    out.extend(1u16.to_be_bytes());
+    out.extend(0u16.to_be_bytes());
+    out.extend((entries.len() as u32).to_be_bytes());
+    for (name, (r, g, b)) in entries {
+        let mut block = Vec::new();
+        let name: Vec<u16> = name.encode_utf16().chain([0]).collect();
+        block.extend((name.len() as u16).to_be_bytes());
+        name.iter().for_each(|c| block.extend(c.to_be_bytes()));
+        block.extend(b"RGB ");
+        for v in [r, g, b] {
+            block.extend((v as f32 / 255.0).to_be_bytes());
+        }
+        // global color
+        block.extend(0u16.to_be_bytes());
+
+        out.extend(1u16.to_be_bytes());
+        out.extend((block.len() as u32).to_be_bytes());
+        out.extend(block);
+    }
+
+    out
+}
+
+// background/foreground band on top of two rows of eight swatches
+fn swatch(theme: &Theme, path: &str) -> Result<(), String> {
+    let size = 64;
+    let mut img = RgbImage::new(size * 8, size * 3);
+    for (x, y, pixel) in img.enumerate_pixels_mut() {
+        let (r, g, b) = match y / size {
+            0 if x < size * 4 => theme.special[0],
+            0 => theme.special[1],
+            row => theme.colors[((row - 1) * 8 + x / size) as usize],
+        };
+        *pixel = Rgb([r, g, b]);
+    }
+
+    img.save_with_format(path, ImageFormat::Png)
+        .map_err(|e| e.to_string())
+}
+
+pub fn export_theme(format: &str, output: Option<String>, theme: Option<String>, send: bool) {
+    let theme = match theme {
+        Some(name) => find_theme(&name, send)
+            .ok_or(format!("can't find {name}"))
+            .and_then(|p| read_theme(&p)),
+        // named after the applied theme or wallpaper, not the cache file
+        None => current(send)
+            .map(|entry| entry.theme)
+            .ok_or("nothing has been applied yet".to_string()),
+    }
+    .unwrap_or_else(|e| {
+        warning("Export", &e, send);
+        exit(1)
+    });
+
+    let ext = match format {
+        "base16" => "yaml",
+        "iterm" => "itermcolors",
+        "windows-terminal" => "json",
+        "gimp" => "gpl",
+        "ase" => "ase",
+        "png" => "png",
+        _ => {
+            warning(
+                "Export",
+                &format!(
+                    "unknown format {format} (base16, iterm, windows-terminal, gimp, ase, png)"
+                ),
+                send,
+            );
+            exit(1)
+        }
+    };
+    let output = output.unwrap_or(format!("{}.{ext}", theme.name));
+
+    let result = match format {
+        "base16" => write(&output, base16(&theme)).map_err(|e| e.to_string()),
+        "iterm" => write(&output, iterm(&theme)).map_err(|e| e.to_string()),
+        "windows-terminal" => write(&output, windows_terminal(&theme)).map_err(|e| e.to_string()),
+        "gimp" => write(&output, gimp(&theme)).map_err(|e| e.to_string()),
+        "ase" => write(&output, ase(&theme)).map_err(|e| e.to_string()),
+        _ => swatch(&theme, &output),
+    };
+
+    match result {
+        Ok(_) => info("Export", &format!("saved {output}"), send),
+        Err(e) => {
+            warning("Export", &format!("can't write {output}: {e}"), send);
+            exit(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme() -> Theme {
+        let colors = (0..16).map(|i| (i * 16, 0, 255 - i * 16)).collect();
+        let mut theme = Theme::new("test", colors);
+        theme.special = [(1, 2, 3), (250, 251, 252), (9, 9, 9)];
+        theme
+    }
+
+    #[test]
+    fn exports_base16() {
+        let out = base16(&theme());
+        assert!(out.starts_with("scheme: \"test\"\nauthor: \"walrs\"\n"));
+        assert!(out.contains("base00: \"010203\""));
+        assert!(out.contains("base05: \"fafbfc\""));
+        assert_eq!(out.lines().count(), 18);
+    }
+
+    #[test]
+    fn exports_windows_terminal_and_gimp() {
+        let out = windows_terminal(&theme());
+        assert!(out.contains("\"name\": \"test\""));
+        assert!(out.contains("\"cursorColor\": \"#090909\""));
+        assert!(out.contains("\"brightWhite\": \"#f0000f\""));
+
+        let out = gimp(&theme());
+        assert!(out.starts_with("GIMP Palette\nName: test\n"));
+        assert!(out.contains("  1   2   3\tbackground"));
+        assert_eq!(out.lines().count(), 4 + 19);
+    }
+
+    #[test]
+    fn exports_ase() {
+        let out = ase(&theme());
+        assert_eq!(&out[..4], b"ASEF");
+        assert_eq!(u32::from_be_bytes(out[8..12].try_into().unwrap()), 19);
+    }
+}
@@ -1,12 +1,17 @@
+use crate::tonal::seed_color;
 use crate::utils::warning;
 use color_thief::ColorFormat;
+use image::DynamicImage;
 use kmeans_colors::get_kmeans;
-use palette::{FromColor, IntoColor, Lab, Srgb};
+use palette::{FromColor, IntoColor, Lab, Mix, Oklab, Oklch, Srgb};
 use palette_extract::{MaxColors, Quality};
 use std::collections::HashSet;
 use std::fs::read;
 use std::process::exit;
 
+// extracted colors, the alpha of the image and the seed for tonal palettes
+pub type Palette = (Vec<(u8, u8, u8)>, u8, Option<(u8, u8, u8)>);
+
 fn adjust_rgb(r: u8, g: u8, b: u8, brightness: i16, saturation: i16) -> (u8, u8, u8) {
     let saturation = saturation + 50;
     let avg = ((r as u16 + g as u16 + b as u16) / 3) as f32;
@@ -37,30 +42,150 @@ fn generate_variation(color: (u8, u8, u8), offset: i16) -> (u8, u8, u8) {
     adjust_rgb(color.0, color.1, color.2, offset, 50)
 }
 
+pub fn mix(a: (u8, u8, u8), b: (u8, u8, u8), v: f32) -> (u8, u8, u8) {
+    let m = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * v).round() as u8;
+    (m(a.0, b.0), m(a.1, b.1), m(a.2, b.2))
+}
+
+fn to_oklch(color: (u8, u8, u8)) -> Oklch {
+    Oklch::from_color(Srgb::new(color.0, color.1, color.2).into_format::<f32>())
+}
+
+// pick the chromatic color whose hue is closest to `hue` (degrees), or shift
+// the best match onto that hue when the palette has nothing close enough
+fn nearest_hue(candidates: &[(u8, u8, u8)], hue: f32) -> (u8, u8, u8) {
+    let distance = |c: &(u8, u8, u8)| {
+        let lch = to_oklch(*c);
+        let d = (lch.hue.into_positive_degrees() - hue).abs();
+        // greys have no meaningful hue, push them to the back
+        d.min(360.0 - d) + if lch.chroma < 0.03 { 360.0 } else { 0.0 }
+    };
+    let best = *candidates
+        .iter()
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .unwrap();
+    if distance(&best) <= 45.0 {
+        return best;
+    }
+
+    let lch = to_oklch(best);
+    let shifted = Oklch::new(lch.l.clamp(0.55, 0.8), lch.chroma.max(0.12), hue);
+    let rgb = Srgb::from_color(shifted).into_format::<u8>();
+    (rgb.red, rgb.green, rgb.blue)
+}
+
+// derive named UI roles from the 16 ANSI colors, so templates don't need to
+// hand-pick indices; empty when the palette is too short to have fg/bg
+pub fn semantic_roles(colors: &[(u8, u8, u8)]) -> Vec<(&'static str, (u8, u8, u8))> {
+    if colors.len() < 8 {
+        return Vec::new();
+    }
+    let (background, foreground) = (colors[0], colors[7]);
+
+    let mut chromatic = colors[1..7].to_vec();
+    chromatic.sort_by(|a, b| to_oklch(*b).chroma.total_cmp(&to_oklch(*a).chroma));
+    let accent = chromatic[0];
+    let accent_alt = chromatic
+        .iter()
+        .copied()
+        .find(|c| {
+            let d = (to_oklch(*c).hue - to_oklch(accent).hue)
+                .into_degrees()
+                .abs();
+            d > 30.0
+        })
+        .unwrap_or(chromatic[1]);
+
+    vec![
+        ("accent", accent),
+        ("accent_alt", accent_alt),
+        ("surface", mix(background, foreground, 0.05)),
+        ("surface_variant", mix(background, foreground, 0.12)),
+        ("border", mix(background, foreground, 0.25)),
+        ("selection", mix(background, accent, 0.35)),
+        ("error", nearest_hue(&colors[1..7], 25.0)),
+        ("warning", nearest_hue(&colors[1..7], 75.0)),
+        ("success", nearest_hue(&colors[1..7], 145.0)),
+        ("info", nearest_hue(&colors[1..7], 245.0)),
+        ("muted", mix(foreground, background, 0.4)),
+    ]
+}
+
+// build xterm colors 16-255 from the theme instead of the stock values: the
+// 6x6x6 cube interpolates between background, the six base colors and
+// foreground, and the gray ramp runs from background to foreground
+pub fn extended_palette(colors: &[(u8, u8, u8)]) -> Vec<(u8, u8, u8)> {
+    if colors.len() < 8 {
+        return Vec::new();
+    }
+    let lab = |i: usize| {
+        let (r, g, b) = colors[i];
+        Oklab::from_color(Srgb::new(r, g, b).into_format::<f32>())
+    };
+    let rgb = |c: Oklab| {
+        let c = Srgb::from_color(c).into_format::<u8>();
+        (c.red, c.green, c.blue)
+    };
+    // corners of the cube, indexed by the red/green/blue bits like ANSI colors
+    let corners: Vec<Oklab> = (0..8).map(lab).collect();
+
+    let mut extended = Vec::with_capacity(240);
+    for r in 0..6 {
+        for g in 0..6 {
+            for b in 0..6 {
+                let (r, g, b) = (r as f32 / 5.0, g as f32 / 5.0, b as f32 / 5.0);
+                let c0 = corners[0].mix(corners[1], r);
+                let c1 = corners[2].mix(corners[3], r);
+                let c2 = corners[4].mix(corners[5], r);
+                let c3 = corners[6].mix(corners[7], r);
+                let c = c0.mix(c1, g).mix(c2.mix(c3, g), b);
+                extended.push(rgb(c));
+            }
+        }
+    }
+    for i in 0..24 {
+        let v = (i + 1) as f32 / 25.0;
+        extended.push(rgb(corners[0].mix(corners[7], v)));
+    }
+
+    extended
+}
+
+// open an image, falling back to sniffing the format from its content
+pub fn open_image(image_path: &str) -> Option<DynamicImage> {
+    image::open(image_path).ok().or_else(|| {
+        let data = read(image_path).ok()?;
+        let fmt = image::guess_format(&data).ok()?;
+        image::load_from_memory_with_format(&data, fmt).ok()
+    })
+}
+
 pub fn get_colors(
     image_path: &str,
     send: bool,
     brightness: Option<i16>,
     saturation: Option<i16>,
-) -> (Vec<(u8, u8, u8)>, u8) {
-    let core_image = match image::open(image_path) {
-        Ok(img) => img,
-        Err(_) => {
-            let data = read(image_path).unwrap();
-            match image::guess_format(&data) {
-                Ok(fmt) => image::load_from_memory_with_format(&data, fmt).unwrap(),
-                Err(_) => {
-                    warning("Image", "Unsupported or corrupted image format", send);
-                    exit(1);
-                }
-            }
-        }
-    };
+) -> Palette {
+    extract_colors(image_path, brightness, saturation).unwrap_or_else(|e| {
+        warning("Image", &e, send);
+        exit(1);
+    })
+}
+
+// get_colors for callers that have to outlive a bad image
+pub fn extract_colors(
+    image_path: &str,
+    brightness: Option<i16>,
+    saturation: Option<i16>,
+) -> Result<Palette, String> {
+    let core_image = open_image(image_path).ok_or(format!(
+        "{image_path}: unsupported or corrupted image format"
+    ))?;
 
     // resize the image
     let image = core_image.resize(
         400,
-        (core_image.height() as f32 * (400 as f32 / core_image.width() as f32)) as u32,
+        (core_image.height() as f32 * (400_f32 / core_image.width() as f32)) as u32,
         image::imageops::FilterType::Lanczos3,
     );
 
@@ -153,5 +278,29 @@ pub fn get_colors(
     (r, g, b) = to_gray(r, g, b, 0.55);
     done[7] = (r, g, b);
     done[15] = (r, g, b);
-    (done, *alpha)
+    Ok((done, *alpha, seed_color(&image)))
+}
+
+// light counterpart of a generated palette: background and foreground swap
+// and the accents are darkened so they stay readable on the light background
+pub fn light_variant(colors: &[(u8, u8, u8)]) -> Vec<(u8, u8, u8)> {
+    let (background, foreground) = (colors[0], colors[15]);
+    let darken = |c: (u8, u8, u8)| {
+        let mut lch = to_oklch(c);
+        lch.l = lch.l.min(0.55);
+        let rgb = Srgb::from_color(lch).into_format::<u8>();
+        (rgb.red, rgb.green, rgb.blue)
+    };
+
+    colors
+        .iter()
+        .enumerate()
+        .map(|(i, c)| match i {
+            0 => mix(foreground, (255, 255, 255), 0.5),
+            7 => mix(background, (0, 0, 0), 0.2),
+            8 => mix(background, foreground, 0.5),
+            15 => background,
+            _ => darken(*c),
+        })
+        .collect()
 }
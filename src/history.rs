@@ -0,0 +1,241 @@
+use crate::create_templates::create_template;
+use crate::preview::swatches;
+use crate::reload::reload;
+use crate::theme::{
+    Theme, current_theme, current_theme_name, parse_hex, parse_json, read_theme,
+    set_current_theme_name,
+};
+use crate::utils::{command_output, get_cache, info, warning, write_atomic};
+use std::fs::{create_dir_all, read_dir, read_to_string, remove_file};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// oldest entries are dropped past this
+const MAX_HISTORY: usize = 50;
+
+// a scheme that was applied, with what is needed to apply it again
+pub struct Entry {
+    pub theme: Theme,
+    pub wallpaper: String,
+    pub alpha: u8,
+    pub extended: bool,
+    // extraction settings, only known for schemes generated from an image
+    pub brightness: Option<i16>,
+    pub saturation: Option<i16>,
+    // seed for the tonal palettes, taken from the wallpaper when extracting
+    pub seed: Option<(u8, u8, u8)>,
+    pub timestamp: u64,
+}
+
+impl Entry {
+    pub fn new(theme: &Theme, wallpaper: &str, alpha: u8, extended: bool) -> Self {
+        Self {
+            theme: theme.clone(),
+            wallpaper: wallpaper.to_string(),
+            alpha,
+            extended,
+            brightness: None,
+            saturation: None,
+            seed: None,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+fn history_dir(send: bool) -> PathBuf {
+    get_cache(send).join("walrs").join("history")
+}
+
+// newest first
+fn entry_paths(send: bool) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = read_dir(history_dir(send))
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == "json"))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths.reverse();
+
+    paths
+}
+
+pub fn read_entry(path: &Path) -> Option<Entry> {
+    let map = parse_json(&read_to_string(path).ok()?);
+    let mut theme = read_theme(path).ok()?;
+    theme.name = map.get("name").cloned().unwrap_or_default();
+
+    Some(Entry {
+        theme,
+        wallpaper: map.get("wallpaper").cloned().unwrap_or("None".to_string()),
+        alpha: map.get("alpha").and_then(|a| a.parse().ok()).unwrap_or(100),
+        extended: map.get("extended").is_some_and(|e| e == "true"),
+        brightness: map.get("brightness").and_then(|b| b.parse().ok()),
+        saturation: map.get("saturation").and_then(|s| s.parse().ok()),
+        seed: map.get("seed").and_then(|s| parse_hex(s)),
+        timestamp: map
+            .get("timestamp")
+            .and_then(|t| t.parse().ok())
+            .unwrap_or(0),
+    })
+}
+
+// theme json with the wallpaper and options on top
+pub fn entry_json(entry: &Entry) -> String {
+    let json = entry.theme.to_json();
+    let mut extra = format!(
+        "{{\n    \"wallpaper\": \"{}\",\n    \"alpha\": \"{}\",\n    \"extended\": \"{}\",\n    \"timestamp\": \"{}\",\n",
+        entry.wallpaper.replace('\\', "\\\\").replace('"', "\\\""),
+        entry.alpha,
+        entry.extended,
+        entry.timestamp
+    );
+    for (key, value) in [
+        ("brightness", entry.brightness),
+        ("saturation", entry.saturation),
+    ] {
+        if let Some(value) = value {
+            extra += &format!("    \"{key}\": \"{value}\",\n");
+        }
+    }
+    if let Some((r, g, b)) = entry.seed {
+        extra += &format!("    \"seed\": \"#{r:02x}{g:02x}{b:02x}\",\n");
+    }
+    json.replacen("{\n", &extra, 1)
+}
+
+// the scheme applied right now
+pub fn latest(send: bool) -> Option<Entry> {
+    entry_paths(send).first().and_then(|p| read_entry(p))
+}
+
+// what is applied right now: the newest history entry, or the cache when
+// the scheme was applied before history existed
+pub fn current(send: bool) -> Option<Entry> {
+    if let Some(entry) = latest(send) {
+        return Some(entry);
+    }
+
+    let cache = get_cache(send).join("wal");
+    let mut theme = current_theme(send).ok()?;
+    let wallpaper = read_to_string(cache.join("wal"))
+        .ok()
+        .and_then(|w| w.lines().next().map(|l| l.trim().to_string()))
+        .unwrap_or("None".to_string());
+    // the cache file is called colors, name it like a fresh apply would
+    theme.name = current_theme_name(send)
+        .or_else(|| {
+            let stem = Path::new(&wallpaper).file_stem()?.to_str()?;
+            (wallpaper != "None").then(|| stem.to_string())
+        })
+        .unwrap_or("walrs".to_string());
+    let alpha = read_to_string(cache.join("colors.json"))
+        .ok()
+        .and_then(|c| parse_json(&c).get("alpha")?.parse().ok())
+        .unwrap_or(100);
+
+    Some(Entry::new(
+        &theme,
+        &wallpaper,
+        alpha,
+        cache.join("colors-256").exists(),
+    ))
+}
+
+// remember an applied scheme, skipping repeats of the latest one
+pub fn record(entry: &Entry, send: bool) {
+    let dir = history_dir(send);
+    if create_dir_all(&dir).is_err() {
+        warning("History", "can't create the history directory", send);
+        return;
+    }
+
+    let paths = entry_paths(send);
+    if let Some(latest) = paths.first().and_then(|p| read_entry(p))
+        && latest.theme.colors == entry.theme.colors
+        && latest.theme.special == entry.theme.special
+        && latest.wallpaper == entry.wallpaper
+    {
+        return;
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let path = dir.join(format!("{:020}.json", now.as_millis()));
+    if write_atomic(&path, &entry_json(entry)).is_err() {
+        warning("History", "can't save the history entry", send);
+    }
+
+    for old in paths.iter().skip(MAX_HISTORY - 1) {
+        let _ = remove_file(old);
+    }
+}
+
+pub fn apply(entry: Entry, scripts: bool, ttys: &[u32], send: bool) {
+    let from_theme = entry.wallpaper == "None";
+    create_template(
+        (entry.theme.colors.clone(), entry.alpha),
+        entry.theme.special,
+        &entry.wallpaper,
+        entry.seed,
+        entry.extended,
+        send,
+    );
+    set_current_theme_name(from_theme.then_some(entry.theme.name.as_str()), send);
+    reload(send, from_theme, scripts, ttys);
+}
+
+pub fn print_history(send: bool) {
+    let entries: Vec<Entry> = entry_paths(send)
+        .iter()
+        .filter_map(|p| read_entry(p))
+        .collect();
+    if entries.is_empty() {
+        info("History", "nothing applied yet", send);
+        return;
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        let date = command_output("date", &["-d", &format!("@{}", entry.timestamp), "+%F %R"])
+            .unwrap_or_default();
+        println!(
+            "{i:3}  {}  {:<28}{}",
+            date.trim(),
+            entry.theme.name,
+            swatches(&entry.theme)
+        );
+    }
+}
+
+// apply entry n (0 is the current scheme), it becomes the newest entry again
+pub fn apply_history(index: usize, scripts: bool, ttys: &[u32], send: bool) {
+    let Some(entry) = entry_paths(send).get(index).and_then(|p| read_entry(p)) else {
+        warning("History", &format!("no entry {index}"), send);
+        exit(1)
+    };
+
+    info("History", &format!("applying {}", entry.theme.name), send);
+    record(&entry, send);
+    apply(entry, scripts, ttys, send);
+}
+
+// drop the current scheme and go back to the one before it
+pub fn undo(scripts: bool, ttys: &[u32], send: bool) {
+    let paths = entry_paths(send);
+    let Some(previous) = paths.get(1).and_then(|p| read_entry(p)) else {
+        warning("History", "nothing to undo", send);
+        exit(1)
+    };
+
+    let _ = remove_file(&paths[0]);
+    info("History", &format!("back to {}", previous.theme.name), send);
+    apply(previous, scripts, ttys, send);
+}
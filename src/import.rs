@@ -0,0 +1,403 @@
+use crate::theme::{Theme, parse_hex, parse_json, save_theme};
+use crate::utils::{info, warning};
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::process::exit;
+
+// base16-shell slot order: color0-15 taken from these base16 keys
+const BASE16_SLOTS: [&str; 16] = [
+    "base00", "base08", "base0B", "base0A", "base0D", "base0E", "base0C", "base05", "base03",
+    "base08", "base0B", "base0A", "base0D", "base0E", "base0C", "base07",
+];
+
+// base24 has dedicated bright colors for color9-14
+const BASE24_BRIGHT: [&str; 6] = ["base12", "base14", "base13", "base16", "base17", "base15"];
+
+const ANSI_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    for q in ['"', '\''] {
+        if let Some(rest) = value.strip_prefix(q) {
+            return rest.split(q).next().unwrap_or(rest);
+        }
+    }
+    // unquoted values can still carry a trailing comment
+    value.split(" #").next().unwrap_or(value).trim()
+}
+
+// find a value by key, keys are matched on their last dotted segments
+fn find<'a>(map: &'a HashMap<String, String>, key: &str) -> Option<&'a String> {
+    let key = key.to_lowercase();
+    let suffix = format!(".{key}");
+    map.iter()
+        .find(|(k, _)| {
+            let k = k.to_lowercase();
+            k == key || k.ends_with(&suffix)
+        })
+        .map(|(_, v)| v)
+}
+
+// look up every key in order
+fn pick(map: &HashMap<String, String>, keys: &[String]) -> Result<Vec<(u8, u8, u8)>, String> {
+    keys.iter()
+        .map(|key| {
+            let v = find(map, key).ok_or(format!("missing {key}"))?;
+            parse_hex(v).ok_or(format!("invalid color for {key}: {v}"))
+        })
+        .collect()
+}
+
+fn indexed_keys(prefix: &str) -> Vec<String> {
+    (0..16).map(|i| format!("{prefix}{i}")).collect()
+}
+
+// indented `key: value` reader, nested maps become dotted keys; covers
+// base16/base24 schemes (classic and `palette:` layouts) and alacritty.yml
+fn parse_yaml(content: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    let mut parents: Vec<(usize, String)> = Vec::new();
+
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let indent = line.len() - trimmed.len();
+        while parents.last().is_some_and(|(i, _)| *i >= indent) {
+            parents.pop();
+        }
+
+        let key = unquote(key).to_string();
+        let value = unquote(value);
+        if value.is_empty() {
+            parents.push((indent, key));
+        } else {
+            let mut path: Vec<&str> = parents.iter().map(|(_, k)| k.as_str()).collect();
+            path.push(&key);
+            map.insert(path.join("."), value.to_string());
+        }
+    }
+
+    map
+}
+
+// `[section]` + `key = value` reader for alacritty.toml
+fn parse_toml(content: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    let mut section = String::new();
+
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.trim_matches(['[', ']']).to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            map.insert(
+                format!("{section}.{}", unquote(key)),
+                unquote(value).to_string(),
+            );
+        }
+    }
+
+    map
+}
+
+// `name value` lines from kitty.conf and `*name: value` from Xresources
+fn parse_lines(content: &str, separator: &[char]) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.starts_with('#') && !l.starts_with('!'))
+        .filter_map(|l| l.split_once(separator))
+        .map(|(k, v)| {
+            // drop resource prefixes like `*`, `*.` or `URxvt*`
+            let k = k.trim().rsplit(['*', '.']).next().unwrap_or_default();
+            (k.to_string(), unquote(v).to_string())
+        })
+        .collect()
+}
+
+// iTerm2 plist: `<key>Ansi 0 Color</key>` followed by a dict of components
+fn parse_plist(content: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    let tag = |s: &str, name: &str| -> Option<(String, usize)> {
+        let open = format!("<{name}>");
+        let close = format!("</{name}>");
+        let start = s.find(&open)? + open.len();
+        let end = start + s[start..].find(&close)?;
+        Some((s[start..end].trim().to_string(), end + close.len()))
+    };
+
+    let mut rest = content;
+    while let Some((name, end)) = tag(rest, "key") {
+        rest = &rest[end..];
+        if !rest.trim_start().starts_with("<dict>") {
+            continue;
+        }
+        let Some(dict_end) = rest.find("</dict>") else {
+            break;
+        };
+        let dict = &rest[..dict_end];
+
+        let component = |key: &str| -> Option<u8> {
+            let at = dict.find(&format!("<key>{key} Component</key>"))?;
+            let (v, _) = tag(&dict[at..], "real")?;
+            Some((v.parse::<f32>().ok()?.clamp(0.0, 1.0) * 255.0).round() as u8)
+        };
+        if let (Some(r), Some(g), Some(b)) =
+            (component("Red"), component("Green"), component("Blue"))
+        {
+            map.insert(name, format!("#{r:02x}{g:02x}{b:02x}"));
+        }
+        rest = &rest[dict_end..];
+    }
+
+    map
+}
+
+fn is_base24(map: &HashMap<String, String>) -> bool {
+    map.keys().any(|k| k.ends_with("base12"))
+        || map
+            .iter()
+            .any(|(k, v)| k.ends_with("system") && v == "base24")
+}
+
+fn from_base16(map: &HashMap<String, String>) -> Result<Vec<(u8, u8, u8)>, String> {
+    let mut slots: Vec<String> = BASE16_SLOTS.iter().map(|s| s.to_string()).collect();
+    if is_base24(map) {
+        for (i, key) in BASE24_BRIGHT.iter().enumerate() {
+            slots[9 + i] = key.to_string();
+        }
+    }
+
+    pick(map, &slots)
+}
+
+fn from_alacritty(map: &HashMap<String, String>) -> Result<Vec<(u8, u8, u8)>, String> {
+    let keys: Vec<String> = ["normal", "bright"]
+        .iter()
+        .flat_map(|group| ANSI_NAMES.iter().map(move |n| format!("{group}.{n}")))
+        .collect();
+
+    pick(map, &keys)
+}
+
+fn from_windows_terminal(map: &HashMap<String, String>) -> Result<Vec<(u8, u8, u8)>, String> {
+    let names = ANSI_NAMES.map(|n| if n == "magenta" { "purple" } else { n });
+    let mut keys: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+    keys.extend(names.iter().map(|n| {
+        let mut n = n.to_string();
+        n[..1].make_ascii_uppercase();
+        format!("bright{n}")
+    }));
+
+    pick(map, &keys)
+}
+
+fn from_iterm(map: &HashMap<String, String>) -> Result<Vec<(u8, u8, u8)>, String> {
+    let keys: Vec<String> = (0..16).map(|i| format!("Ansi {i} Color")).collect();
+
+    pick(map, &keys)
+}
+
+pub fn import_theme(file: &str, name: Option<String>, send: bool) {
+    let path = Path::new(file);
+    let content = read_to_string(path).unwrap_or_else(|_| {
+        warning("Import", &format!("can't read {file}"), send);
+        exit(1)
+    });
+
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let file_name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(|s| s.trim_start_matches('.'))
+        .unwrap_or("imported");
+    let mut default_name = stem.to_string();
+
+    // background, foreground and cursor keys of each format
+    let mut special = ["background", "foreground", "cursor"];
+    let map;
+    let colors = match ext.as_str() {
+        "yaml" | "yml" => {
+            map = parse_yaml(&content);
+            if map.keys().any(|k| k.ends_with("base00")) {
+                let prefix = if is_base24(&map) { "base24" } else { "base16" };
+                default_name = format!("{prefix}-{stem}");
+                special = ["base00", "base05", "base05"];
+                from_base16(&map)
+            } else {
+                special = ["primary.background", "primary.foreground", "cursor.cursor"];
+                from_alacritty(&map)
+            }
+        }
+        "toml" => {
+            map = parse_toml(&content);
+            special = ["primary.background", "primary.foreground", "cursor.cursor"];
+            from_alacritty(&map)
+        }
+        "itermcolors" => {
+            map = parse_plist(&content);
+            special = ["Background Color", "Foreground Color", "Cursor Color"];
+            from_iterm(&map)
+        }
+        "json" => {
+            map = parse_json(&content);
+            if map.contains_key("color0") {
+                pick(&map, &indexed_keys("color"))
+            } else {
+                special[2] = "cursorColor";
+                from_windows_terminal(&map)
+            }
+        }
+        "conf" => {
+            map = parse_lines(&content, &[' ', '\t']);
+            pick(&map, &indexed_keys("color"))
+        }
+        _ if file_name.contains("xresources")
+            || file_name.contains("xdefaults")
+            || content.contains("color0:") =>
+        {
+            map = parse_lines(&content, &[':']);
+            special[2] = "cursorColor";
+            pick(&map, &indexed_keys("color"))
+        }
+        _ => {
+            warning(
+                "Import",
+                &format!("unsupported scheme format: {file}"),
+                send,
+            );
+            exit(1)
+        }
+    };
+
+    let colors = colors.unwrap_or_else(|e| {
+        warning("Import", &format!("{file}: {e}"), send);
+        exit(1)
+    });
+
+    let mut theme = Theme::new(&name.unwrap_or(default_name), colors);
+    theme.author = find(&map, "author").cloned();
+    for (i, key) in special.iter().enumerate() {
+        if let Some(color) = find(&map, key).and_then(|v| parse_hex(v)) {
+            theme.special[i] = color;
+        }
+    }
+
+    let saved = save_theme(&theme, send);
+    info(
+        "Import",
+        &format!("saved {} to {}", theme.name, saved.display()),
+        send,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base16_yaml() -> String {
+        let mut content = "scheme: \"Test\"\nauthor: \"me\"\n".to_string();
+        for i in 0..16 {
+            content += &format!("base0{i:X}: \"{:02x}0000\" # slot {i}\n", i * 16);
+        }
+        content
+    }
+
+    #[test]
+    fn imports_base16_yaml() {
+        let colors = from_base16(&parse_yaml(&base16_yaml())).unwrap();
+        assert_eq!(colors.len(), 16);
+        assert_eq!(colors[0], (0x00, 0, 0));
+        assert_eq!(colors[1], (0x80, 0, 0));
+        assert_eq!(colors[15], (0x70, 0, 0));
+    }
+
+    #[test]
+    fn imports_nested_yaml() {
+        let mut content = "colors:\n".to_string();
+        for group in ["normal", "bright"] {
+            content += &format!("  {group}:\n");
+            for name in ANSI_NAMES {
+                content += &format!("    {name}: '#102030'\n");
+            }
+        }
+        let colors = from_alacritty(&parse_yaml(&content)).unwrap();
+        assert_eq!(colors, vec![(0x10, 0x20, 0x30); 16]);
+    }
+
+    #[test]
+    fn rejects_incomplete_yaml() {
+        let content = base16_yaml().replace("base08", "baseXX");
+        let err = from_base16(&parse_yaml(&content)).unwrap_err();
+        assert!(err.contains("missing base08"), "{err}");
+    }
+
+    #[test]
+    fn imports_toml() {
+        let mut content = String::new();
+        for group in ["normal", "bright"] {
+            content += &format!("[colors.{group}]\n");
+            for name in ANSI_NAMES {
+                content += &format!("{name} = \"0xffffff\"\n");
+            }
+        }
+        let colors = from_alacritty(&parse_toml(&content)).unwrap();
+        assert_eq!(colors[8], (0xff, 0xff, 0xff));
+
+        let broken = content.replacen("0xffffff", "nope", 1);
+        let err = from_alacritty(&parse_toml(&broken)).unwrap_err();
+        assert!(err.contains("invalid color"), "{err}");
+    }
+
+    #[test]
+    fn imports_plist() {
+        let content = "<plist><dict>\
+            <key>Ansi 0 Color</key>\
+            <dict>\
+            <key>Blue Component</key><real>0</real>\
+            <key>Green Component</key><real>0.5</real>\
+            <key>Red Component</key><real>1</real>\
+            </dict></dict></plist>";
+        let map = parse_plist(content);
+        assert_eq!(map.get("Ansi 0 Color").map(String::as_str), Some("#ff8000"));
+
+        // an unterminated dict yields nothing rather than garbage
+        let map = parse_plist("<key>Ansi 0 Color</key><dict><key>Red Component</key>");
+        assert!(map.is_empty());
+        assert!(from_iterm(&map).is_err());
+    }
+
+    #[test]
+    fn imports_kitty_and_xresources() {
+        let map = parse_lines("# comment\ncolor0 #000000\nforeground  #ffffff\n", &[' ']);
+        assert_eq!(map.get("color0").map(String::as_str), Some("#000000"));
+        assert_eq!(map.get("foreground").map(String::as_str), Some("#ffffff"));
+
+        let map = parse_lines(
+            "! comment\n*.color1: #ff0000\nURxvt*color2: #00ff00\n",
+            &[':'],
+        );
+        assert_eq!(map.get("color1").map(String::as_str), Some("#ff0000"));
+        assert_eq!(map.get("color2").map(String::as_str), Some("#00ff00"));
+        assert!(pick(&map, &indexed_keys("color")).is_err());
+    }
+}
@@ -1,37 +1,100 @@
+mod appearance;
 mod create_templates;
+mod daemon;
+mod export;
 mod get_colors;
+mod history;
+mod import;
+mod preview;
 mod reload;
+mod schedule;
+mod snapshot;
+mod terminal;
 mod theme;
+mod tonal;
 mod utils;
 mod wallpaper;
+mod watch;
 
+use appearance::{apply_image, follow, prefers_dark};
 use argh::FromArgs;
-use create_templates::create_template;
+use create_templates::render_templates;
+use daemon::{Slideshow, control, run_daemon};
+use export::export_theme;
 use get_colors::get_colors;
+use history::{apply_history, print_history, undo};
+use import::import_theme;
+use preview::preview_theme;
 use reload::reload;
-use std::fs::{copy, create_dir_all};
+use schedule::run_schedule;
+use snapshot::{load_snapshot, save_snapshot};
+use std::path::Path;
 use std::process::exit;
-use theme::{print_themes, set_theme, theme_exists};
+use terminal::parse_tty;
+use theme::{
+    apply_match, current_theme, match_themes, print_themes, save_theme, set_theme, validate_themes,
+};
 use utils::*;
+use wallpaper::set_wallpaper;
+use watch::watch;
 
 #[derive(FromArgs)]
 #[argh(description = "walrs - Generate colorscheme from image")]
 struct Arg {
-    #[argh(option, short = 'i', description = "path to image or directory")]
-    image: Option<String>,
+    #[argh(
+        switch,
+        short = 'S',
+        long = "scripts",
+        description = "skip running scripts in ~/.config/walrs/scripts/"
+    )]
+    skip_scripts: bool,
 
     #[argh(
         switch,
-        short = 'r',
-        description = "reload without changing the wallpaper"
+        short = 'W',
+        long = "walless",
+        description = "skip changing the wallpaper"
+    )]
+    walless: bool,
+
+    #[argh(
+        switch,
+        short = 'e',
+        long = "extended",
+        description = "also generate colors 16-255 from the palette"
     )]
+    extended: bool,
+
+    #[argh(
+        option,
+        long = "tty",
+        description = "only recolor this terminal, e.g. /dev/pts/3 (repeatable)"
+    )]
+    tty: Vec<String>,
+
+    #[argh(
+        switch,
+        short = 'q',
+        long = "quiet",
+        description = "set quit mode (no output)"
+    )]
+    quit: bool,
+
+    #[argh(switch, short = 'v', long = "version", description = "show version")]
+    version: bool,
+
+    // the flags below are kept for compatibility and map to subcommands
+    #[argh(option, short = 'i', description = "deprecated, use generate -i")]
+    image: Option<String>,
+
+    #[argh(switch, short = 'r', description = "deprecated, use reload")]
     reload: bool,
 
     #[argh(
         switch,
         short = 'R',
         long = "reload-no",
-        description = "will be removed in the next update; use -w instead"
+        description = "deprecated, use -W reload"
     )]
     reload_no: bool,
 
@@ -39,15 +102,22 @@ struct Arg {
         option,
         short = 't',
         long = "theme",
-        description = "use external theme file from .config/walrs/colorschemes"
+        description = "deprecated, use theme apply or theme list"
     )]
     theme: Option<String>,
 
+    #[argh(
+        option,
+        long = "filter",
+        description = "deprecated, use theme apply --filter"
+    )]
+    filter: Option<String>,
+
     #[argh(
         option,
         short = 'g',
         long = "generate",
-        description = "generate & save theme to .config/walrs/colorschemes"
+        description = "deprecated, use theme save"
     )]
     generate: Option<String>,
 
@@ -55,7 +125,7 @@ struct Arg {
         option,
         short = 's',
         long = "saturation",
-        description = "set saturation value (-128 to 127)"
+        description = "deprecated, use generate -s"
     )]
     saturation: Option<i16>,
 
@@ -63,130 +133,663 @@ struct Arg {
         option,
         short = 'b',
         long = "brightness",
+        description = "deprecated, use generate -b"
+    )]
+    brightness: Option<i16>,
+
+    #[argh(
+        switch,
+        short = 'A',
+        long = "appearance",
+        description = "deprecated, use generate -A"
+    )]
+    appearance: bool,
+
+    #[argh(
+        option,
+        long = "order",
+        description = "deprecated, use the --order of generate, wallpaper or daemon"
+    )]
+    order: Option<String>,
+
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Generate(GenerateCommand),
+    Theme(ThemeCommand),
+    Reload(ReloadCommand),
+    Template(TemplateCommand),
+    Wallpaper(WallpaperCommand),
+    Follow(FollowCommand),
+    Schedule(ScheduleCommand),
+    Undo(UndoCommand),
+    History(HistoryCommand),
+    Save(SaveCommand),
+    Load(LoadCommand),
+    Daemon(DaemonCommand),
+    Ctl(CtlCommand),
+    Watch(WatchCommand),
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "generate",
+    description = "generate a colorscheme from an image and apply it"
+)]
+struct GenerateCommand {
+    #[argh(option, short = 'i', description = "path to image or directory")]
+    image: String,
+
+    #[argh(
+        option,
+        short = 's',
+        description = "set saturation value (-128 to 127)"
+    )]
+    saturation: Option<i16>,
+
+    #[argh(
+        option,
+        short = 'b',
         description = "set brightness value (-128 to 127)"
     )]
     brightness: Option<i16>,
 
     #[argh(
         switch,
-        short = 'S',
-        long = "scripts",
-        description = "skip running scripts in ~/.config/walrs/scripts/"
+        short = 'A',
+        description = "use the light or dark variant matching the system preference"
+    )]
+    appearance: bool,
+
+    #[argh(
+        option,
+        description = "how to pick from a wallpaper folder: random, name, mtime, next or previous"
+    )]
+    order: Option<String>,
+
+    #[argh(switch, description = "also pick from subfolders, following symlinks")]
+    recursive: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "reload",
+    description = "apply the current colors and wallpaper again"
+)]
+struct ReloadCommand {}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "template",
+    description = "render templates again from the current colors"
+)]
+struct TemplateCommand {
+    #[argh(positional, description = "only render this template")]
+    name: Option<String>,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "wallpaper",
+    description = "set the wallpaper without changing colors, or print the current one"
+)]
+struct WallpaperCommand {
+    #[argh(positional, description = "path to image or directory")]
+    image: Option<String>,
+
+    #[argh(
+        option,
+        description = "how to pick from a wallpaper folder: random, name, mtime, next or previous"
     )]
-    run_scripts: Option<bool>,
+    order: Option<String>,
+
+    #[argh(switch, description = "also pick from subfolders, following symlinks")]
+    recursive: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "theme", description = "manage colorschemes")]
+struct ThemeCommand {
+    #[argh(subcommand)]
+    action: ThemeAction,
+}
 
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum ThemeAction {
+    List(ThemeList),
+    Preview(ThemePreview),
+    Import(ThemeImport),
+    Validate(ThemeValidate),
+    Match(ThemeMatch),
+    Apply(ThemeApply),
+    Export(ThemeExport),
+    Save(ThemeSave),
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "list", description = "list installed themes")]
+struct ThemeList {
     #[argh(
         switch,
-        short = 'W',
-        long = "walless",
-        description = "skip changing the wallpaper"
+        short = 'p',
+        description = "show color swatches next to each theme"
+    )]
+    preview: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "preview",
+    description = "print a theme's colors without applying it"
+)]
+struct ThemePreview {
+    #[argh(positional, description = "theme name")]
+    name: String,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "import",
+    description = "import a base16/base24, iTerm2, Windows Terminal, Alacritty, kitty, Xresources or pywal scheme"
+)]
+struct ThemeImport {
+    #[argh(positional, description = "scheme file to import")]
+    file: String,
+
+    #[argh(option, short = 'n', description = "name to save the theme as")]
+    name: Option<String>,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "validate",
+    description = "check installed themes (or the given files) for errors"
+)]
+struct ThemeValidate {
+    #[argh(positional, description = "theme files to check")]
+    files: Vec<String>,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "match",
+    description = "rank installed themes by how well they fit a wallpaper"
+)]
+struct ThemeMatch {
+    #[argh(option, short = 'i', description = "wallpaper to match against")]
+    image: String,
+
+    #[argh(
+        option,
+        short = 'n',
+        default = "5",
+        description = "number of themes to list (default 5)"
     )]
-    walless: Option<bool>,
+    count: usize,
 
     #[argh(
         switch,
-        short = 'q',
-        long = "quiet",
-        description = "set quit mode (no output)"
+        short = 'a',
+        description = "apply the best match together with the wallpaper"
     )]
-    quit: bool,
+    apply: bool,
+}
 
-    #[argh(switch, short = 'v', long = "version", description = "show version")]
-    version: bool,
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "apply",
+    description = "apply an installed theme, a dark:light pair or random, random-dark, random-light"
+)]
+struct ThemeApply {
+    #[argh(positional, description = "theme name")]
+    name: String,
+
+    #[argh(
+        option,
+        description = "only pick random themes matching this glob, e.g. 'base16-gruvbox*'"
+    )]
+    filter: Option<String>,
 }
 
-fn main() {
-    // get and load args from user
-    let arg: Arg = argh::from_env();
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "save",
+    description = "save the current colors as a theme in .config/walrs/colorschemes"
+)]
+struct ThemeSave {
+    #[argh(positional, description = "theme name")]
+    name: String,
+}
 
-    // save the quit status
-    let send = !arg.quit;
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "export",
+    description = "export the current scheme to another format"
+)]
+struct ThemeExport {
+    #[argh(
+        option,
+        short = 'f',
+        description = "base16, iterm, windows-terminal, gimp, ase or png"
+    )]
+    format: String,
 
-    // print the version
-    if arg.version {
-        info("Version", env!("CARGO_PKG_VERSION"), send);
-        exit(0);
-    }
+    #[argh(option, short = 'o', description = "output file")]
+    output: Option<String>,
 
-    // this will be removed next update
-    if arg.reload_no {
+    #[argh(option, short = 't', description = "export an installed theme instead")]
+    theme: Option<String>,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "follow",
+    description = "keep switching between light and dark with the system preference"
+)]
+struct FollowCommand {
+    #[argh(
+        option,
+        short = 't',
+        description = "theme pair, e.g. gruvbox-dark:gruvbox-light"
+    )]
+    theme: Option<String>,
+
+    #[argh(
+        option,
+        short = 'i',
+        description = "wallpaper to generate both variants from"
+    )]
+    image: Option<String>,
+
+    #[argh(
+        option,
+        short = 'n',
+        default = "5",
+        description = "seconds between checks (default 5)"
+    )]
+    interval: u64,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "schedule",
+    description = "switch themes or wallpapers at the times in ~/.config/walrs/schedule"
+)]
+struct ScheduleCommand {
+    #[argh(switch, short = 'l', description = "print today's schedule and exit")]
+    list: bool,
+
+    #[argh(switch, description = "apply the current entry and exit")]
+    once: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "undo",
+    description = "go back to the previously applied scheme"
+)]
+struct UndoCommand {}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "history",
+    description = "list recently applied schemes"
+)]
+struct HistoryCommand {
+    #[argh(subcommand)]
+    action: Option<HistoryAction>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum HistoryAction {
+    Apply(HistoryApply),
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "apply",
+    description = "apply a scheme from the history"
+)]
+struct HistoryApply {
+    #[argh(positional, description = "entry number from walrs history")]
+    index: usize,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "save",
+    description = "save the current palette, wallpaper and options as a snapshot"
+)]
+struct SaveCommand {
+    #[argh(positional, description = "snapshot name")]
+    name: String,
+
+    #[argh(
+        switch,
+        short = 'c',
+        description = "keep a copy of the wallpaper with the snapshot"
+    )]
+    copy: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "load",
+    description = "apply a saved snapshot, or list them without a name"
+)]
+struct LoadCommand {
+    #[argh(positional, description = "snapshot name")]
+    name: Option<String>,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "daemon",
+    description = "cycle wallpapers and colors from a folder"
+)]
+struct DaemonCommand {
+    #[argh(option, short = 'i', description = "wallpaper folder")]
+    image: String,
+
+    #[argh(
+        option,
+        description = "how to pick the next wallpaper: random, name, mtime, next or previous"
+    )]
+    order: Option<String>,
+
+    #[argh(
+        option,
+        default = "String::from(\"30m\")",
+        description = "time between wallpapers, e.g. 45s, 30m or 2h (default 30m)"
+    )]
+    interval: String,
+
+    #[argh(switch, description = "also pick from subfolders, following symlinks")]
+    recursive: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "ctl",
+    description = "control the running daemon: next, prev, pause, resume or status"
+)]
+struct CtlCommand {
+    #[argh(positional, description = "command to send")]
+    command: String,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "watch",
+    description = "re-render templates and reload terminals as files change"
+)]
+struct WatchCommand {}
+
+// turn the old flat flags into the matching subcommand
+fn legacy_command(arg: &mut Arg, send: bool) -> Option<Command> {
+    let deprecated = |flag: &str, instead: &str| {
         warning(
-            "Reload",
-            "this will be removed in the next update, use -W instead",
+            "Args",
+            &format!("{flag} will be removed in the next release, use walrs {instead}"),
             send,
-        );
-        reload(send, true, arg.run_scripts.unwrap_or(false));
-        exit(0);
+        )
+    };
+
+    if arg.reload_no {
+        deprecated("-R", "-W reload");
+        arg.walless = true;
+        return Some(Command::Reload(ReloadCommand {}));
     }
 
-    // reload colors with setting wallpaper
     if arg.reload {
-        reload(
-            send,
-            arg.walless.unwrap_or(false),
-            arg.run_scripts.unwrap_or(false),
-        );
-        exit(0);
+        deprecated("-r", "reload");
+        return Some(Command::Reload(ReloadCommand {}));
     }
 
-    // if user didn't type any thing
-    if arg.image.is_none() && arg.theme.is_none() && arg.generate.is_none() {
-        warning("Args", "run: walrs --help", send);
-        exit(1);
+    if let Some(name) = arg.theme.take() {
+        let action = if name == "themes" {
+            deprecated("-t themes", "theme list");
+            ThemeAction::List(ThemeList { preview: false })
+        } else {
+            deprecated("-t", "theme apply");
+            ThemeAction::Apply(ThemeApply {
+                name,
+                filter: arg.filter.take(),
+            })
+        };
+        return Some(Command::Theme(ThemeCommand { action }));
     }
 
-    // show or set theme from user
-    if let Some(v) = arg.theme {
-        let config = get_config(send);
-        if v == "themes" {
-            print_themes(send);
-        } else if theme_exists(&config) {
-            set_theme(v, send, arg.run_scripts.unwrap_or(false));
-        } else {
-            let colorschemes_dir = config.join("walrs").join("colorschemes");
-            create_dir_all(&colorschemes_dir).unwrap();
-            let walrs_cache = share_files();
-            if !theme_exists(walrs_cache.parent().unwrap()) {
-                warning("theme", "Can't find configuration directory", send);
-                exit(1)
+    if let Some(name) = arg.generate.take() {
+        deprecated("-g", "theme save");
+        return Some(Command::Theme(ThemeCommand {
+            action: ThemeAction::Save(ThemeSave { name }),
+        }));
+    }
+
+    let image = arg.image.take()?;
+    deprecated("-i", "generate -i");
+    Some(Command::Generate(GenerateCommand {
+        image,
+        saturation: arg.saturation,
+        brightness: arg.brightness,
+        appearance: arg.appearance,
+        order: arg.order.take(),
+        // the old flag searched subfolders too
+        recursive: true,
+    }))
+}
+
+// the global switches may also follow the command, e.g. walrs reload -W, so
+// move them in front of it before parsing
+fn hoist_switches(args: Vec<String>) -> Vec<String> {
+    let mut args = args.into_iter();
+    let mut switches: Vec<String> = args.next().into_iter().collect();
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => {
+                rest.push(arg);
+                rest.extend(args.by_ref());
+            }
+            "-W" | "--walless" | "-S" | "--scripts" | "-e" | "--extended" | "-q" | "--quiet" => {
+                switches.push(arg)
+            }
+            "--tty" => {
+                switches.push(arg);
+                switches.extend(args.next());
             }
-            run(&format!(
-                "cp -r {}/* {}",
-                walrs_cache.join("colorschemes").display(),
-                colorschemes_dir.display()
-            ));
-            set_theme(v, send, arg.run_scripts.unwrap_or(false));
+            _ => rest.push(arg),
         }
-        exit(0);
     }
+    switches.extend(rest);
 
-    // generate a new theme from current colors
-    if let Some(v) = arg.generate {
-        let dis = get_config(send).join("walrs").join("colorschemes");
-        create_dir_all(dis.join("dark")).unwrap();
-        copy(
-            get_cache(send).join("wal").join("colors"),
-            dis.join("dark").join(v),
-        )
-        .unwrap();
-        info("Generate", "generate colors", send);
+    switches
+}
+
+fn main() {
+    // get and load args from user
+    let args = hoist_switches(std::env::args().collect());
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let cmd = Path::new(args[0])
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(args[0]);
+    let mut arg: Arg = Arg::from_args(&[cmd], &args[1..]).unwrap_or_else(|early| {
+        match early.status {
+            Ok(()) => println!("{}", early.output),
+            Err(()) => eprintln!("{}\nRun {cmd} --help for more information.", early.output),
+        }
+        exit(early.status.map_or(1, |_| 0))
+    });
+
+    // save the quit status
+    let send = !arg.quit;
+
+    // print the version
+    if arg.version {
+        info("Version", env!("CARGO_PKG_VERSION"), send);
         exit(0);
+    }
+
+    // terminals picked with --tty, every terminal when empty
+    let ttys: Vec<u32> = arg
+        .tty
+        .iter()
+        .map(|t| {
+            parse_tty(t).unwrap_or_else(|| {
+                warning("Terminal", &format!("invalid terminal {t}"), send);
+                exit(1)
+            })
+        })
+        .collect();
+
+    let Some(command) = arg
+        .command
+        .take()
+        .or_else(|| legacy_command(&mut arg, send))
+    else {
+        warning("Args", "run: walrs --help", send);
+        exit(1)
     };
+    let scripts = arg.skip_scripts;
 
-    // analyze the image and generate the palette
-    if arg.image.is_some() {
-        let image_path = image_path(arg.image, send);
-        let palette = get_colors(&image_path, send, arg.brightness, arg.saturation);
-        info("Generate", "generate colors", send);
+    match command {
+        Command::Generate(v) => {
+            // analyze the image and generate the palette
+            let image_path = image_path(Some(v.image), v.order.as_deref(), v.recursive, send);
+            let palette = get_colors(&image_path, send, v.brightness, v.saturation);
+            info("Generate", "generate colors", send);
 
-        create_template(palette, &image_path, send);
-        info("Template", "create templates", send);
+            let dark = !v.appearance || prefers_dark();
+            apply_image(
+                &palette,
+                &image_path,
+                dark,
+                (v.brightness, v.saturation),
+                arg.extended,
+                send,
+            );
+            info("Template", "create templates", send);
 
-        reload(
+            reload(send, arg.walless, scripts, &ttys);
+            print_colors(send);
+        }
+        Command::Theme(theme) => match theme.action {
+            ThemeAction::List(v) => print_themes(send, v.preview),
+            ThemeAction::Preview(v) => preview_theme(&v.name, send),
+            ThemeAction::Import(v) => import_theme(&v.file, v.name, send),
+            ThemeAction::Validate(v) => validate_themes(v.files, send),
+            ThemeAction::Match(v) => {
+                let image = image_path(Some(v.image), None, false, send);
+                let mut best = match_themes(&image, v.count, send);
+                if v.apply {
+                    best.extended = arg.extended;
+                    apply_match(best, arg.walless, scripts, &ttys, send);
+                }
+            }
+            ThemeAction::Apply(v) => {
+                set_theme(v.name, v.filter, send, scripts, arg.extended, &ttys)
+            }
+            ThemeAction::Export(v) => export_theme(&v.format, v.output, v.theme, send),
+            ThemeAction::Save(v) => {
+                // save the current colors as a new theme
+                let mut theme = current_theme(send).unwrap_or_else(|_| {
+                    warning("Theme", "can't read the current colors", send);
+                    exit(1)
+                });
+                theme.name = v.name;
+                let saved = save_theme(&theme, send);
+                info(
+                    "Theme",
+                    &format!("saved {} to {}", theme.name, saved.display()),
+                    send,
+                );
+            }
+        },
+        Command::Reload(_) => reload(send, arg.walless, scripts, &ttys),
+        Command::Template(v) => render_templates(v.name, send),
+        Command::Wallpaper(v) => set_wallpaper(v.image, v.order.as_deref(), v.recursive, send),
+        Command::Follow(v) => follow(
+            v.theme,
+            v.image.map(|i| image_path(Some(i), None, false, send)),
+            v.interval,
+            scripts,
+            arg.extended,
+            &ttys,
+            send,
+        ),
+        Command::Schedule(v) => run_schedule(v.list, v.once, scripts, arg.extended, &ttys, send),
+        Command::Undo(_) => undo(scripts, &ttys, send),
+        Command::History(v) => match v.action {
+            Some(HistoryAction::Apply(v)) => apply_history(v.index, scripts, &ttys, send),
+            None => print_history(send),
+        },
+        Command::Daemon(v) => run_daemon(
+            Slideshow {
+                dir: &v.image,
+                interval: &v.interval,
+                order: v.order.or(arg.order).as_deref(),
+                recursive: v.recursive,
+                walless: arg.walless,
+                scripts,
+                extended: arg.extended,
+                ttys: &ttys,
+            },
             send,
-            arg.walless.unwrap_or(false),
-            arg.run_scripts.unwrap_or(false),
+        ),
+        Command::Ctl(v) => control(&v.command, send),
+        Command::Watch(_) => watch(scripts, &ttys, send),
+        Command::Save(v) => save_snapshot(&v.name, v.copy, send),
+        Command::Load(v) => load_snapshot(v.name, scripts, &ttys, send),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hoist_switches;
+
+    #[test]
+    fn hoists_global_switches() {
+        let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+        assert_eq!(
+            hoist_switches(args("walrs theme apply nord -S --tty /dev/pts/3 -q")),
+            args("walrs -S --tty /dev/pts/3 -q theme apply nord")
         );
-        print_colors(send);
-    };
+        assert_eq!(
+            hoist_switches(args("walrs -W generate -i a.png -e")),
+            args("walrs -W -e generate -i a.png")
+        );
+        assert_eq!(
+            hoist_switches(args("walrs ctl -- -q")),
+            args("walrs ctl -- -q")
+        );
+    }
 }
@@ -0,0 +1,107 @@
+use crate::theme::{Theme, find_theme, read_theme};
+use crate::utils::warning;
+use std::process::exit;
+
+const WIDTH: usize = 48;
+
+fn fg((r, g, b): (u8, u8, u8)) -> String {
+    format!("\x1b[38;2;{r};{g};{b}m")
+}
+
+fn bg((r, g, b): (u8, u8, u8)) -> String {
+    format!("\x1b[48;2;{r};{g};{b}m")
+}
+
+// one line of 16 small swatches, used next to theme names
+pub fn swatches(theme: &Theme) -> String {
+    let mut out: String = theme
+        .colors
+        .iter()
+        .map(|c| format!("{}  ", bg(*c)))
+        .collect();
+    out += "\x1b[0m";
+    out
+}
+
+// a line drawn on the theme background, `parts` are (color index, text)
+fn line(theme: &Theme, parts: &[(Option<usize>, &str)]) -> String {
+    let [background, foreground, _] = theme.special;
+    let mut out = format!("{} ", bg(background));
+    let mut len = 1;
+    for (color, text) in parts {
+        let color = color.map_or(foreground, |i| theme.colors[i]);
+        out += &format!("{}{text}", fg(color));
+        len += text.chars().count();
+    }
+    out += &" ".repeat(WIDTH.saturating_sub(len));
+    out += "\x1b[0m";
+    out
+}
+
+// print swatches and a fake terminal session in the theme's colors
+pub fn print_preview(theme: &Theme) {
+    let [background, foreground, cursor] = theme.special;
+    println!("[\x1b[33m{}\x1b[0m]", theme.name);
+
+    for row in theme.colors.chunks(8) {
+        let cells: String = row.iter().map(|c| format!("{}      ", bg(*c))).collect();
+        println!("{cells}\x1b[0m");
+    }
+    print!("{}{}  background  ", bg(background), fg(foreground));
+    print!("{}{}  foreground  ", bg(foreground), fg(background));
+    println!("{}{}  cursor  \x1b[0m", bg(cursor), fg(background));
+    println!();
+
+    let session: [&[(Option<usize>, &str)]; 12] = [
+        &[
+            (Some(2), "user@host"),
+            (None, " "),
+            (Some(4), "~/src/walrs"),
+            (Some(5), " (main)"),
+            (None, " $ ls"),
+        ],
+        &[
+            (Some(4), "src/  "),
+            (Some(4), "templates/  "),
+            (Some(2), "build.sh  "),
+            (Some(6), "latest -> v1.1"),
+        ],
+        &[(None, "Cargo.toml  README.md  "), (Some(1), "core.dump")],
+        &[
+            (Some(2), "user@host"),
+            (None, " "),
+            (Some(4), "~/src/walrs"),
+            (Some(5), " (main)"),
+            (None, " $ git diff"),
+        ],
+        &[(None, "diff --git a/src/main.rs b/src/main.rs")],
+        &[(Some(6), "@@ -1,4 +1,4 @@")],
+        &[(None, " use std::env;")],
+        &[(Some(1), "-let theme = \"dark\";")],
+        &[(Some(2), "+let theme = \"light\";")],
+        &[(Some(8), " // unchanged comment")],
+        &[(Some(3), "warning"), (None, ": unused variable `x`")],
+        &[
+            (Some(2), "user@host"),
+            (None, " "),
+            (Some(4), "~/src/walrs"),
+            (Some(5), " (main)"),
+            (None, " $ "),
+        ],
+    ];
+    for parts in session {
+        println!("{}", line(theme, parts));
+    }
+}
+
+pub fn preview_theme(name: &str, send: bool) {
+    let theme = find_theme(name, send)
+        .ok_or(format!("can't find {name}"))
+        .and_then(|p| read_theme(&p))
+        .unwrap_or_else(|e| {
+            warning("Preview", &e, send);
+            exit(1)
+        });
+
+    print_preview(&theme);
+}
@@ -1,40 +1,110 @@
-use crate::utils::{get_cache, get_config, info, run, share_files, warning};
+use crate::get_colors::{mix, semantic_roles};
+use crate::terminal::{
+    excluded_programs, foreground, multiplexer, owned_by_user, passthrough, processes,
+};
+use crate::theme::{Theme, current_theme};
+use crate::utils::{get_cache, get_config, info, run, share_files, warning, write_atomic};
 use crate::wallpaper::change_wallpaper;
-use std::fs::{create_dir_all, OpenOptions};
+use std::fs::{OpenOptions, create_dir_all};
 use std::fs::{read_dir, read_to_string};
 use std::io::Write;
 use std::path::Path;
 use std::process::exit;
 
-fn colors(colors: Vec<String>, send: bool) {
+// OSC codes of the special colors, taken from the scheme written with the
+// colors and falling back to the plain palette
+fn special_colors(colors: &[String], scheme: Option<&Theme>) -> Vec<(&'static str, String)> {
+    let hex = |(r, g, b): (u8, u8, u8)| format!("#{r:02x}{g:02x}{b:02x}");
+    let (background, foreground, cursor, selection) = match scheme {
+        Some(theme) => {
+            let [background, foreground, cursor] = theme.special;
+            let selection = semantic_roles(&theme.colors)
+                .into_iter()
+                .find(|(name, _)| *name == "selection")
+                .map_or(mix(background, foreground, 0.25), |(_, c)| c);
+            (
+                hex(background),
+                hex(foreground),
+                hex(cursor),
+                hex(selection),
+            )
+        }
+        None => (
+            colors[0].clone(),
+            colors[7].clone(),
+            colors[7].clone(),
+            colors[8].clone(),
+        ),
+    };
+
+    vec![
+        ("10", foreground.clone()),
+        ("11", background.clone()),
+        ("12", cursor),
+        // mouse pointer foreground and background
+        ("13", foreground.clone()),
+        ("14", background.clone()),
+        // selection background and foreground
+        ("17", selection),
+        ("19", foreground.clone()),
+        // bold and border (urxvt)
+        ("706", foreground.clone()),
+        ("708", background),
+        // bold and underline special indices (xterm, kitty)
+        ("4;256", foreground.clone()),
+        ("4;257", foreground),
+    ]
+}
+
+// every escape sequence needed to recolor a terminal: the palette followed
+// by the special colors
+fn sequences(colors: &[String], special: &[(&str, String)]) -> Vec<String> {
+    let mut sequences: Vec<String> = colors
+        .iter()
+        .enumerate()
+        .map(|(i, value)| format!("\x1b]4;{i};{value}\x1b\\"))
+        .collect();
+    for (code, value) in special {
+        sequences.push(format!("\x1b]{code};{value}\x1b\\"));
+    }
+
+    sequences
+}
+
+fn colors(colors: Vec<String>, scheme: Option<&Theme>, cache: &Path, ttys: &[u32], send: bool) {
+    let sequences = sequences(&colors, &special_colors(&colors, scheme));
+    let processes = processes();
+    let excluded = excluded_programs(send);
+
+    // saved so new terminals can `cat ~/.cache/wal/sequences` on startup
+    if write_atomic(&cache.join("sequences"), &sequences.concat()).is_err() {
+        warning("Colors", "Can't write the sequences file", send);
+    }
+
     for i in read_dir("/dev/pts/").unwrap_or_else(|_| {
         warning("Terminal", "Unable to find open terminals", send);
         exit(1)
     }) {
         let file = i.unwrap().file_name().into_string().unwrap();
-        if file != "ptmx" && file.parse::<i32>().is_ok() {
-            let special_index = [(10, 7), (11, 0), (12, 15), (708, 5)];
-            let term = file.parse::<i32>().unwrap();
-            for (i, value) in colors.iter().enumerate() {
-                let sequence = format!("\x1b]4;{i};{value}\x1b\\");
-                if let Ok(mut file) = OpenOptions::new()
-                    .write(true)
-                    .open(format!("/dev/pts/{term}"))
-                {
-                    file.write_all(sequence.as_bytes()).unwrap_or_else(|_| {
-                        warning("Colors", "Can't apply terminal colors", send);
-                    });
-                };
+        if file != "ptmx" && file.parse::<u32>().is_ok() {
+            let term = file.parse::<u32>().unwrap();
+            // explicit --tty terminals skip the exclusion list
+            let skip = if ttys.is_empty() {
+                foreground(term, &processes).is_some_and(|p| excluded.iter().any(|e| e == p))
+            } else {
+                !ttys.contains(&term)
+            };
+            if skip || !owned_by_user(term) {
+                continue;
             }
+            let sequence = passthrough(&sequences, multiplexer(term, &processes));
             if let Ok(mut file) = OpenOptions::new()
                 .write(true)
                 .open(format!("/dev/pts/{term}"))
             {
-                for (i, v) in special_index {
-                    let sequence = format!("\x1b]{};{}\x1b\\", i, colors[v]);
-
-                    let _ = file.write_all(sequence.as_bytes());
-                }
+                file.write_all(sequence.as_bytes()).unwrap_or_else(|_| {
+                    warning("Colors", "Can't apply terminal colors", send);
+                });
             }
         }
     }
@@ -55,13 +125,13 @@ pub fn get_wallpaper(cache: &Path, send: bool) -> String {
         .to_string()
 }
 
-pub fn reload(send: bool, set_wal: bool, run_scripts: bool) {
+pub fn reload(send: bool, set_wal: bool, run_scripts: bool, ttys: &[u32]) {
     let cache = get_cache(send).join("wal");
     let walrs_cache = share_files();
     let file_path = cache.join("colors");
 
     // read the colors file and load all the colors
-    let lines: Vec<String> = std::fs::read_to_string(&file_path)
+    let mut lines: Vec<String> = std::fs::read_to_string(&file_path)
         .unwrap_or_else(|_| {
             warning("Colors", "can't read colors", send);
             exit(1)
@@ -70,13 +140,22 @@ pub fn reload(send: bool, set_wal: bool, run_scripts: bool) {
         .map(|line| line.to_string())
         .collect();
 
+    // extended colors 16-255, only written when generated with -e
+    if lines.len() == 16
+        && let Ok(extended) = read_to_string(cache.join("colors-256"))
+    {
+        lines.extend(extended.lines().map(|line| line.to_string()));
+    }
+
     // applie the wallpaper
     if !set_wal {
-        change_wallpaper(&get_wallpaper(&cache.clone(), send.clone()), send.clone())
+        change_wallpaper(&get_wallpaper(&cache, send), send)
     }
 
-    // change terminal colors
-    colors(lines, send);
+    // change terminal colors, with the specials of colors.json which is
+    // written together with the colors
+    let scheme = current_theme(send).ok();
+    colors(lines, scheme.as_ref(), &cache, ttys, send);
 
     if !run_scripts {
         // initial scripts files
@@ -123,3 +202,26 @@ pub fn reload(send: bool, set_wal: bool, run_scripts: bool) {
     }
     info("Colors", "colorscheme applied successfully", send);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::special_colors;
+    use crate::theme::Theme;
+
+    #[test]
+    fn takes_specials_from_the_scheme() {
+        let colors: Vec<String> = (0..16).map(|i| format!("#0000{i:02x}")).collect();
+        let special = |scheme| {
+            special_colors(&colors, scheme)
+                .into_iter()
+                .filter(|(code, _)| ["10", "11", "12"].contains(code))
+                .map(|(_, value)| value)
+                .collect::<Vec<_>>()
+        };
+
+        let mut theme = Theme::new("t", vec![(0, 0, 0); 16]);
+        theme.special = [(1, 2, 3), (4, 5, 6), (7, 8, 9)];
+        assert_eq!(special(Some(&theme)), ["#040506", "#010203", "#070809"]);
+        assert_eq!(special(None), ["#000007", "#000000", "#000007"]);
+    }
+}
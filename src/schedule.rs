@@ -0,0 +1,302 @@
+use crate::appearance::{apply_image, pick_pair};
+use crate::get_colors::extract_colors;
+use crate::reload::reload;
+use crate::theme::{apply_theme, find_theme, install_themes, is_random, load_theme};
+use crate::utils::{command_output, find_image, get_config, info, warning};
+use std::f32::consts::PI;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::process::exit;
+use std::thread::sleep;
+use std::time::Duration;
+
+// one line of ~/.config/walrs/schedule: `<when> theme|wallpaper <value>`
+struct Entry {
+    line: usize,
+    when: String,
+    kind: String,
+    value: String,
+}
+
+struct Schedule {
+    latitude: Option<f32>,
+    longitude: Option<f32>,
+    entries: Vec<Entry>,
+}
+
+fn read_schedule(path: &Path, send: bool) -> Result<Schedule, String> {
+    let content = read_to_string(path).map_err(|_| format!("can't read {}", path.display()))?;
+    let mut schedule = Schedule {
+        latitude: None,
+        longitude: None,
+        entries: Vec::new(),
+    };
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = || format!("{}:{}: invalid line '{line}'", path.display(), i + 1);
+        let mut parts = line.splitn(3, char::is_whitespace).map(str::trim);
+        let (Some(when), Some(kind)) = (parts.next(), parts.next()) else {
+            return Err(error());
+        };
+        let value = parts.next().unwrap_or_default().to_string();
+
+        match (when, kind) {
+            ("latitude", v) => schedule.latitude = Some(v.parse().map_err(|_| error())?),
+            ("longitude", v) => schedule.longitude = Some(v.parse().map_err(|_| error())?),
+            (_, "theme" | "wallpaper") if !value.is_empty() && parse_when(when).is_some() => {
+                schedule.entries.push(Entry {
+                    line: i + 1,
+                    when: when.to_string(),
+                    kind: kind.to_string(),
+                    value,
+                })
+            }
+            _ => return Err(error()),
+        }
+    }
+
+    // catch what would otherwise only fail when the entry comes up
+    let located = schedule.latitude.is_some() && schedule.longitude.is_some();
+    for entry in &schedule.entries {
+        let at = format!("{}:{}", path.display(), entry.line);
+        let sun = parse_when(&entry.when).is_some_and(|(anchor, _)| !anchor.is_empty());
+        if sun && !located {
+            return Err(format!("{at}: {} needs latitude and longitude", entry.when));
+        }
+        if entry.kind == "theme" {
+            let (dark, light) = entry
+                .value
+                .split_once(':')
+                .unwrap_or((&entry.value, &entry.value));
+            for name in [dark, light] {
+                if !is_random(name) && find_theme(name, send).is_none() {
+                    return Err(format!("{at}: unknown theme '{name}'"));
+                }
+            }
+        }
+    }
+    if schedule.entries.is_empty() {
+        return Err(format!("{}: nothing scheduled", path.display()));
+    }
+
+    Ok(schedule)
+}
+
+// "07:30", "sunrise", "sunset+30" or "sunrise-15" as (anchor, minutes)
+fn parse_when(when: &str) -> Option<(&str, i32)> {
+    for anchor in ["sunrise", "sunset"] {
+        if let Some(offset) = when.strip_prefix(anchor) {
+            let offset = match offset {
+                "" => 0,
+                _ => offset.trim_start_matches('+').parse().ok()?,
+            };
+            return Some((anchor, offset));
+        }
+    }
+
+    let (h, m) = when.split_once(':')?;
+    let (h, m): (i32, i32) = (h.parse().ok()?, m.parse().ok()?);
+    ((0..24).contains(&h) && (0..60).contains(&m)).then_some(("", h * 60 + m))
+}
+
+// sunrise and sunset in minutes after local midnight, NOAA's approximation;
+// None during polar day or night
+fn sun_times(day: u32, latitude: f32, longitude: f32, utc_offset: i32) -> Option<(i32, i32)> {
+    let g = 2.0 * PI / 365.0 * (day as f32 - 0.5);
+    let eqtime = 229.18
+        * (0.000075 + 0.001868 * g.cos()
+            - 0.032077 * g.sin()
+            - 0.014615 * (2.0 * g).cos()
+            - 0.040849 * (2.0 * g).sin());
+    let decl = 0.006918 - 0.399912 * g.cos() + 0.070257 * g.sin() - 0.006758 * (2.0 * g).cos()
+        + 0.000907 * (2.0 * g).sin()
+        - 0.002697 * (3.0 * g).cos()
+        + 0.00148 * (3.0 * g).sin();
+
+    let lat = latitude.to_radians();
+    let cos_ha = 90.833_f32.to_radians().cos() / (lat.cos() * decl.cos()) - lat.tan() * decl.tan();
+    if !(-1.0..=1.0).contains(&cos_ha) {
+        return None;
+    }
+    let ha = cos_ha.acos().to_degrees();
+
+    let local = |utc: f32| (utc.round() as i32 + utc_offset).rem_euclid(24 * 60);
+    Some((
+        local(720.0 - 4.0 * (longitude + ha) - eqtime),
+        local(720.0 - 4.0 * (longitude - ha) - eqtime),
+    ))
+}
+
+// day of the year, minutes after midnight and the UTC offset in minutes
+fn local_time() -> Option<(u32, i32, i32)> {
+    let now = command_output("date", &["+%j %H %M %z"])?;
+    let mut parts = now.split_whitespace();
+    let day = parts.next()?.parse().ok()?;
+    let minutes = parts.next()?.parse::<i32>().ok()? * 60 + parts.next()?.parse::<i32>().ok()?;
+    let zone = parts.next()?;
+    let sign = if zone.starts_with('-') { -1 } else { 1 };
+    let zone = zone.trim_start_matches(['+', '-']);
+    let offset = zone.get(..2)?.parse::<i32>().ok()? * 60 + zone.get(2..4)?.parse::<i32>().ok()?;
+
+    Some((day, minutes, sign * offset))
+}
+
+// today's entries with their time, sorted; sun entries are dropped during
+// polar day or night
+fn resolve(schedule: &Schedule, day: u32, utc_offset: i32, send: bool) -> Vec<(i32, usize)> {
+    let sun = match (schedule.latitude, schedule.longitude) {
+        (Some(lat), Some(lon)) => sun_times(day, lat, lon, utc_offset),
+        _ => None,
+    };
+
+    let mut times: Vec<(i32, usize)> = schedule
+        .entries
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| {
+            let (anchor, minutes) = parse_when(&entry.when)?;
+            let base = match (anchor, sun) {
+                ("", _) => 0,
+                ("sunrise", Some((rise, _))) => rise,
+                ("sunset", Some((_, set))) => set,
+                _ => {
+                    warning("Schedule", &format!("no {anchor} today"), send);
+                    return None;
+                }
+            };
+            Some(((base + minutes).rem_euclid(24 * 60), i))
+        })
+        .collect();
+    times.sort_by_key(|(time, _)| *time);
+
+    times
+}
+
+// a missing wallpaper or theme only skips the entry, the schedule keeps going
+fn apply(
+    entry: &Entry,
+    scripts: bool,
+    extended: bool,
+    ttys: &[u32],
+    send: bool,
+) -> Result<(), String> {
+    info(
+        "Schedule",
+        &format!("{}: {} {}", entry.when, entry.kind, entry.value),
+        send,
+    );
+    if entry.kind == "theme" {
+        let theme = load_theme(&pick_pair(&entry.value), None, send)?;
+        apply_theme(theme, scripts, extended, ttys, send);
+    } else {
+        let image = find_image(Some(entry.value.clone()), None, false, send)?;
+        let palette = extract_colors(&image, None, None)?;
+        apply_image(&palette, &image, true, (None, None), extended, send);
+        reload(send, false, scripts, ttys);
+    }
+    Ok(())
+}
+
+pub fn run_schedule(
+    list: bool,
+    once: bool,
+    scripts: bool,
+    extended: bool,
+    ttys: &[u32],
+    send: bool,
+) {
+    install_themes(send);
+    let path = get_config(send).join("walrs").join("schedule");
+    let schedule = read_schedule(&path, send).unwrap_or_else(|e| {
+        warning("Schedule", &e, send);
+        exit(1)
+    });
+    let mut last: Option<usize> = None;
+    let mut today: Option<(u32, Vec<(i32, usize)>)> = None;
+
+    loop {
+        let Some((day, now, offset)) = local_time() else {
+            warning("Schedule", "can't read the local time", send);
+            exit(1)
+        };
+        // the times only change with the day, so resolve and warn once a day
+        if today.as_ref().is_none_or(|(d, _)| *d != day) {
+            let times = resolve(&schedule, day, offset, send);
+            if times.is_empty() {
+                warning("Schedule", "nothing scheduled today", send);
+            }
+            today = Some((day, times));
+        }
+        let times = &today.as_ref().unwrap().1;
+        if times.is_empty() {
+            if list || once {
+                exit(1)
+            }
+            sleep(Duration::from_secs(30));
+            continue;
+        }
+
+        if list {
+            for (time, i) in times {
+                let entry = &schedule.entries[*i];
+                println!(
+                    "{:02}:{:02}  {:<12}{} {}",
+                    time / 60,
+                    time % 60,
+                    entry.when,
+                    entry.kind,
+                    entry.value
+                );
+            }
+            return;
+        }
+
+        // the latest entry that already started, wrapping to yesterday's last
+        let (_, current) = times
+            .iter()
+            .rev()
+            .find(|(time, _)| *time <= now)
+            .unwrap_or(&times[times.len() - 1]);
+        if last != Some(*current) {
+            if let Err(e) = apply(&schedule.entries[*current], scripts, extended, ttys, send) {
+                warning("Schedule", &e, send);
+            }
+            last = Some(*current);
+        }
+
+        if once {
+            return;
+        }
+        sleep(Duration::from_secs(30));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_when, sun_times};
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_when("07:30"), Some(("", 450)));
+        assert_eq!(parse_when("sunset+30"), Some(("sunset", 30)));
+        assert_eq!(parse_when("sunrise-15"), Some(("sunrise", -15)));
+        for bad in ["24:00", "7:60", "noon", "sunset+x", "0730"] {
+            assert_eq!(parse_when(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn computes_sun_times() {
+        // Paris at the June solstice, UTC+2: about 05:47 and 21:58
+        let (rise, set) = sun_times(172, 48.85, 2.35, 120).unwrap();
+        assert!((rise - (5 * 60 + 47)).abs() <= 5, "{rise}");
+        assert!((set - (21 * 60 + 58)).abs() <= 5, "{set}");
+
+        // midnight sun above the arctic circle
+        assert_eq!(sun_times(172, 80.0, 15.0, 120), None);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::history::{apply, current, entry_json, read_entry, record};
+use crate::utils::{get_config, info, valid_name, warning};
+use std::fs::{copy, create_dir_all, read_dir, write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+fn snapshot_dir(send: bool) -> PathBuf {
+    get_config(send).join("walrs").join("snapshots")
+}
+
+fn check_name(name: &str, title: &str, send: bool) {
+    if !valid_name(name) {
+        warning(title, &format!("invalid snapshot name '{name}'"), send);
+        exit(1)
+    }
+}
+
+// save the current look, optionally with a copy of the wallpaper so it
+// survives the original being moved
+pub fn save_snapshot(name: &str, copy_wallpaper: bool, send: bool) {
+    check_name(name, "Save", send);
+    let Some(mut entry) = current(send) else {
+        warning("Save", "nothing is applied yet", send);
+        exit(1)
+    };
+    entry.theme.name = name.to_string();
+    let dir = snapshot_dir(send);
+    if create_dir_all(&dir).is_err() {
+        warning("Save", "can't create the snapshots directory", send);
+        exit(1)
+    }
+
+    if copy_wallpaper && entry.wallpaper != "None" {
+        let source = Path::new(&entry.wallpaper);
+        let ext = source.extension().and_then(|e| e.to_str()).unwrap_or("img");
+        let target = dir.join(format!("{name}.{ext}"));
+        if copy(source, &target).is_err() {
+            warning("Save", &format!("can't copy {}", entry.wallpaper), send);
+            exit(1)
+        }
+        entry.wallpaper = target.display().to_string();
+    }
+
+    let path = dir.join(format!("{name}.json"));
+    match write(&path, entry_json(&entry)) {
+        Ok(_) => info("Save", &format!("saved {name} to {}", path.display()), send),
+        Err(e) => {
+            warning(
+                "Save",
+                &format!("can't write {}: {e}", path.display()),
+                send,
+            );
+            exit(1)
+        }
+    }
+}
+
+fn print_snapshots(send: bool) {
+    let mut names: Vec<String> = read_dir(snapshot_dir(send))
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|e| e == "json"))
+                .filter_map(|p| Some(p.file_stem()?.to_str()?.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+
+    if names.is_empty() {
+        info("Load", "no snapshots saved", send);
+    }
+    for name in names {
+        println!("    -{name}");
+    }
+}
+
+pub fn load_snapshot(name: Option<String>, scripts: bool, ttys: &[u32], send: bool) {
+    let Some(name) = name else {
+        print_snapshots(send);
+        return;
+    };
+    check_name(&name, "Load", send);
+    let path = snapshot_dir(send).join(format!("{name}.json"));
+    let Some(mut entry) = read_entry(&path) else {
+        warning("Load", &format!("can't read snapshot {name}"), send);
+        exit(1)
+    };
+
+    if entry.wallpaper != "None" && !Path::new(&entry.wallpaper).exists() {
+        warning(
+            "Load",
+            &format!("{} is missing, keeping the wallpaper", entry.wallpaper),
+            send,
+        );
+        entry.wallpaper = "None".to_string();
+    }
+
+    info("Load", &format!("loading {name}"), send);
+    record(&entry, send);
+    apply(entry, scripts, ttys, send);
+}
@@ -0,0 +1,137 @@
+use crate::utils::get_config;
+use std::collections::HashMap;
+use std::fs::{metadata, read_dir, read_to_string};
+use std::os::unix::fs::MetadataExt;
+
+// the bits of /proc/<pid>/stat needed to tell what runs on a terminal
+pub struct Process {
+    pub ppid: u32,
+    pub tty: u64,
+    pub tpgid: u32,
+    pub comm: String,
+}
+
+pub fn processes() -> HashMap<u32, Process> {
+    let Ok(entries) = read_dir("/proc") else {
+        return HashMap::new();
+    };
+
+    entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let stat = read_to_string(format!("/proc/{pid}/stat")).ok()?;
+            // comm is wrapped in parentheses and may itself contain spaces
+            let (head, rest) = stat.rsplit_once(')')?;
+            let comm = head.split_once('(')?.1.to_string();
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            Some((
+                pid,
+                Process {
+                    ppid: fields.get(1)?.parse().ok()?,
+                    tty: fields.get(4)?.parse().ok()?,
+                    tpgid: fields.get(5)?.parse().unwrap_or(0),
+                    comm,
+                },
+            ))
+        })
+        .collect()
+}
+
+// tty_nr of /dev/pts/N as the kernel encodes it in /proc/<pid>/stat
+pub fn pts_device(pts: u32) -> u64 {
+    let (major, minor) = (136 + pts as u64 / 256, pts as u64 % 256);
+    (major << 8) | minor
+}
+
+// accepts "3", "pts/3" and "/dev/pts/3"
+pub fn parse_tty(tty: &str) -> Option<u32> {
+    tty.trim_start_matches("/dev/")
+        .trim_start_matches("pts/")
+        .parse()
+        .ok()
+}
+
+// only touch terminals opened by the user running walrs
+pub fn owned_by_user(pts: u32) -> bool {
+    match (metadata(format!("/dev/pts/{pts}")), metadata("/proc/self")) {
+        (Ok(pts), Ok(me)) => pts.uid() == me.uid(),
+        _ => false,
+    }
+}
+
+// name of the process group leader in the pts foreground
+pub fn foreground(pts: u32, processes: &HashMap<u32, Process>) -> Option<&str> {
+    let device = pts_device(pts);
+    let tpgid = processes.values().find(|p| p.tty == device)?.tpgid;
+    processes
+        .get(&tpgid)
+        .filter(|p| p.tty == device)
+        .map(|p| p.comm.as_str())
+}
+
+// process names from ~/.config/walrs/exclude-terminals, one per line
+pub fn excluded_programs(send: bool) -> Vec<String> {
+    read_to_string(get_config(send).join("walrs").join("exclude-terminals"))
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+// "tmux" or "screen" when the processes on this pts run inside a multiplexer
+pub fn multiplexer(pts: u32, processes: &HashMap<u32, Process>) -> Option<&'static str> {
+    let device = pts_device(pts);
+    for process in processes.values().filter(|p| p.tty == device) {
+        let mut parent = processes.get(&process.ppid);
+        while let Some(p) = parent {
+            if p.comm.starts_with("tmux") {
+                return Some("tmux");
+            }
+            if p.comm.eq_ignore_ascii_case("screen") {
+                return Some("screen");
+            }
+            parent = processes.get(&p.ppid).filter(|_| p.ppid > 1);
+        }
+    }
+
+    None
+}
+
+// wrap every OSC in the multiplexer's DCS passthrough so it reaches the
+// outer terminal; tmux needs `set -g allow-passthrough on` since 3.3
+pub fn passthrough(sequences: &[String], multiplexer: Option<&str>) -> String {
+    sequences
+        .iter()
+        .map(|s| match multiplexer {
+            Some("tmux") => format!("\x1bPtmux;{}\x1b\\", s.replace('\x1b', "\x1b\x1b")),
+            // screen ends the DCS at the first ST, so the inner OSC ends in BEL
+            Some(_) => {
+                let osc = s.strip_suffix("\x1b\\").unwrap_or(s);
+                format!("\x1bP{osc}\x07\x1b\\")
+            }
+            None => s.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::passthrough;
+
+    #[test]
+    fn wraps_for_multiplexers() {
+        let osc = vec!["\x1b]11;#000000\x1b\\".to_string()];
+        assert_eq!(
+            passthrough(&osc, Some("tmux")),
+            "\x1bPtmux;\x1b\x1b]11;#000000\x1b\x1b\\\x1b\\"
+        );
+        assert_eq!(
+            passthrough(&osc, Some("screen")),
+            "\x1bP\x1b]11;#000000\x07\x1b\\"
+        );
+        assert_eq!(passthrough(&osc, None), osc[0]);
+    }
+}
@@ -1,31 +1,42 @@
 use crate::{
+    appearance::pick_pair,
     create_templates::create_template,
+    get_colors::get_colors,
+    history::{Entry, record},
+    preview::swatches,
     reload::reload,
-    utils::{get_config, run, share_files, warning},
+    utils::{
+        get_cache, get_config, glob_match, info, random_index, run, share_files, valid_name,
+        warning, write_atomic,
+    },
 };
-use std::fs::{create_dir_all, read_dir, read_to_string};
-use std::path::Path;
+use palette::color_difference::Ciede2000;
+use palette::{FromColor, Lab, Srgb};
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_dir, read_to_string, remove_file, write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
 
 pub fn theme_exists(dir: &Path) -> bool {
     dir.join("walrs").join("colorschemes").exists() || dir.join("wal").join("colorschemes").exists()
 }
 
-pub fn print_themes(send: bool) {
-    let (dark, light) = (collect_themes("dark", send), collect_themes("light", send));
-
-    println!("[\x1b[33mDark\x1b[0m]");
-    for theme in dark {
-        println!("    -{theme}")
-    }
-
-    println!("[\x1b[33mLight\x1b[0m]");
-    for theme in light {
-        println!("    -{theme}")
+pub fn print_themes(send: bool, preview: bool) {
+    for (title, variant) in [("Dark", "dark"), ("Light", "light")] {
+        println!("[\x1b[33m{title}\x1b[0m]");
+        for path in theme_paths(variant, send) {
+            let Some(theme) = theme_name(&path) else {
+                continue;
+            };
+            match preview.then(|| read_theme(&path)) {
+                Some(Ok(t)) => println!("    -{theme:<36}{}", swatches(&t)),
+                _ => println!("    -{theme}"),
+            }
+        }
     }
 }
 
-pub fn collect_themes(subdir: &str, send: bool) -> Vec<String> {
+pub fn theme_paths(subdir: &str, send: bool) -> Vec<PathBuf> {
     let base = get_config(send);
     let mut themes = vec![];
     for folder in ["wal", "walrs"] {
@@ -34,7 +45,8 @@ pub fn collect_themes(subdir: &str, send: bool) -> Vec<String> {
             themes.extend(
                 entries
                     .filter_map(|e| e.ok())
-                    .filter_map(|e| e.file_name().into_string().ok()),
+                    .map(|e| e.path())
+                    .filter(|p| p.is_file()),
             );
         }
     }
@@ -42,19 +54,406 @@ pub fn collect_themes(subdir: &str, send: bool) -> Vec<String> {
     themes
 }
 
-fn hex_to_rgb(file: Vec<String>) -> Vec<(u8, u8, u8)> {
-    file.iter()
-        .map(|h| {
-            u32::from_str_radix(&h[1..], 16)
-                .map(|v| ((v >> 16) as u8, (v >> 8 & 0xFF) as u8, (v & 0xFF) as u8))
+pub fn theme_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    Some(name.strip_suffix(".json").unwrap_or(name).to_string())
+}
+
+pub fn collect_themes(subdir: &str, send: bool) -> Vec<String> {
+    theme_paths(subdir, send)
+        .iter()
+        .filter_map(|p| theme_name(p))
+        .collect()
+}
+
+// path of an installed theme, with or without the .json extension
+pub fn find_theme(name: &str, send: bool) -> Option<PathBuf> {
+    ["dark", "light"]
+        .iter()
+        .flat_map(|variant| theme_paths(variant, send))
+        .find(|p| theme_name(p).as_deref() == Some(name))
+}
+
+// parse "#rrggbb", "#rgb", "0xrrggbb" (with or without the prefix) and the
+// X11 "rgb:rr/gg/bb" form with 1-4 hex digits per channel
+pub fn parse_hex(hex: &str) -> Option<(u8, u8, u8)> {
+    let hex = hex.trim();
+
+    if let Some(rest) = hex.strip_prefix("rgb:") {
+        let channel = |c: &str| {
+            if c.is_empty() || c.len() > 4 || !c.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            let v = u32::from_str_radix(c, 16).ok()?;
+            let max = (1u32 << (4 * c.len())) - 1;
+            Some((v * 255 + max / 2) / max)
+        };
+        let mut parts = rest.split('/').map(channel);
+        let (r, g, b) = (parts.next()??, parts.next()??, parts.next()??);
+        return parts
+            .next()
+            .is_none()
+            .then_some((r as u8, g as u8, b as u8));
+    }
+
+    let hex = hex
+        .strip_prefix('#')
+        .or_else(|| hex.strip_prefix("0x"))
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
+    if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let v = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(((v >> 16) as u8, (v >> 8 & 0xFF) as u8, (v & 0xFF) as u8)),
+        3 => {
+            let (r, g, b) = ((v >> 8) as u8, (v >> 4 & 0xF) as u8, (v & 0xF) as u8);
+            Some((r * 17, g * 17, b * 17))
+        }
+        _ => None,
+    }
+}
+
+// legacy themes: one color per line, blank lines and comments are skipped;
+// a line starting with # is a comment unless it holds a color
+fn parse_legacy(content: &str, path: &Path) -> Result<Vec<(u8, u8, u8)>, String> {
+    let mut colors = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let value = line.split_whitespace().next().unwrap_or_default();
+        let comment = line.starts_with("//")
+            || line.starts_with('!')
+            || line.starts_with(';')
+            || (line.starts_with('#') && parse_hex(value).is_none());
+        if line.is_empty() || comment {
+            continue;
+        }
+
+        let color = parse_hex(value).ok_or(format!(
+            "{}:{}: invalid color '{value}'",
+            path.display(),
+            i + 1
+        ))?;
+        if colors.len() < 16 {
+            colors.push(color);
+        }
+    }
+
+    if colors.len() < 16 {
+        return Err(format!(
+            "{}: expected 16 colors, found {}",
+            path.display(),
+            colors.len()
+        ));
+    }
+
+    Ok(colors)
+}
+
+// collect every `"key": "string"` pair regardless of nesting, enough for
+// theme files, windows terminal schemes and pywal's colors.json
+pub fn parse_json(content: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    let mut key: Option<String> = None;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '"' {
+            continue;
+        }
+        let mut string = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => string.extend(chars.next()),
+                '"' => break,
+                _ => string.push(c),
+            }
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek() == Some(&':') {
+            key = Some(string);
+        } else if let Some(k) = key.take() {
+            map.insert(k, string);
+        }
+    }
+
+    map
+}
+
+fn hex(color: (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+}
+
+fn json_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// a colorscheme: 16 ANSI colors plus background, foreground and cursor
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub author: Option<String>,
+    pub special: [(u8, u8, u8); 3],
+    pub colors: Vec<(u8, u8, u8)>,
+}
+
+impl Theme {
+    // legacy themes use color0 as background and color7 as foreground/cursor
+    pub fn new(name: &str, colors: Vec<(u8, u8, u8)>) -> Self {
+        let special = [colors[0], colors[7], colors[7]];
+        Self {
+            name: name.to_string(),
+            author: None,
+            special,
+            colors,
+        }
+    }
+
+    pub fn variant(&self) -> &'static str {
+        let (r, g, b) = self.special[0];
+        let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+        if luma < 128.0 { "dark" } else { "light" }
+    }
+
+    // pywal colors.json layout with walrs' metadata on top
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\n");
+        out += &format!("    \"name\": {},\n", json_string(&self.name));
+        if let Some(author) = &self.author {
+            out += &format!("    \"author\": {},\n", json_string(author));
+        }
+        out += &format!("    \"variant\": \"{}\",\n", self.variant());
+        out += "    \"special\": {\n";
+        for (i, key) in ["background", "foreground", "cursor"].iter().enumerate() {
+            let sep = if i < 2 { "," } else { "" };
+            out += &format!("        \"{key}\": \"{}\"{sep}\n", hex(self.special[i]));
+        }
+        out += "    },\n    \"colors\": {\n";
+        for (i, color) in self.colors.iter().enumerate() {
+            let sep = if i + 1 < self.colors.len() { "," } else { "" };
+            out += &format!("        \"color{i}\": \"{}\"{sep}\n", hex(*color));
+        }
+        out += "    }\n}\n";
+
+        out
+    }
+}
+
+// read a theme file, either the JSON layout or a legacy list of 16 hex lines
+pub fn read_theme(path: &Path) -> Result<Theme, String> {
+    let content = read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let name = theme_name(path).unwrap_or_default();
+
+    if !content.trim_start().starts_with('{') {
+        return Ok(Theme::new(&name, parse_legacy(&content, path)?));
+    }
+
+    // point errors at the line that holds the key
+    let error = |key: &str, message: String| {
+        let line = content
+            .find(&format!("\"{key}\""))
+            .map(|at| content[..at].lines().count().max(1));
+        match line {
+            Some(line) => format!("{}:{line}: {message}", path.display()),
+            None => format!("{}: {message}", path.display()),
+        }
+    };
+    let map = parse_json(&content);
+    let color = |key: &str| -> Result<Option<(u8, u8, u8)>, String> {
+        match map.get(key) {
+            Some(v) => parse_hex(v)
+                .map(Some)
+                .ok_or_else(|| error(key, format!("invalid color '{v}' for {key}"))),
+            None => Ok(None),
+        }
+    };
+
+    let colors = (0..16)
+        .map(|i| {
+            let key = format!("color{i}");
+            color(&key)?.ok_or_else(|| error(&key, format!("missing {key}")))
         })
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap()
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut theme = Theme::new(&name, colors);
+    theme.author = map.get("author").cloned();
+    for (i, key) in ["background", "foreground", "cursor"].iter().enumerate() {
+        if let Some(color) = color(key)? {
+            theme.special[i] = color;
+        }
+    }
+
+    Ok(theme)
+}
+
+// the scheme that is applied right now, from the cache
+pub fn current_theme(send: bool) -> Result<Theme, String> {
+    let cache = get_cache(send).join("wal");
+    read_theme(&cache.join("colors.json")).or_else(|_| read_theme(&cache.join("colors")))
+}
+
+// check every installed theme (or the given files) and report the broken ones
+pub fn validate_themes(files: Vec<String>, send: bool) {
+    let paths: Vec<PathBuf> = if files.is_empty() {
+        ["dark", "light"]
+            .iter()
+            .flat_map(|variant| theme_paths(variant, send))
+            .collect()
+    } else {
+        files.iter().map(PathBuf::from).collect()
+    };
+
+    let mut invalid = 0;
+    for path in &paths {
+        if let Err(e) = read_theme(path) {
+            warning("Validate", &e, send);
+            invalid += 1;
+        }
+    }
+
+    info(
+        "Validate",
+        &format!("{} theme(s) checked, {invalid} invalid", paths.len()),
+        send,
+    );
+    if invalid > 0 {
+        exit(1)
+    }
+}
+
+// store a theme as <name>.json, in dark/ or light/ depending on the background
+pub fn save_theme(theme: &Theme, send: bool) -> PathBuf {
+    if !valid_name(&theme.name) {
+        warning(
+            "Theme",
+            &format!("invalid theme name '{}'", theme.name),
+            send,
+        );
+        exit(1)
+    }
+    let dir = get_config(send)
+        .join("walrs")
+        .join("colorschemes")
+        .join(theme.variant());
+    create_dir_all(&dir).unwrap_or_else(|_| {
+        warning("Theme", "can't create the colorschemes folder", send);
+        exit(1)
+    });
+
+    let path = dir.join(format!("{}.json", theme.name));
+    write(&path, theme.to_json()).unwrap_or_else(|_| {
+        warning("Theme", &format!("can't write {}", path.display()), send);
+        exit(1)
+    });
+
+    path
+}
+
+// name of the last theme applied with theme apply, empty after generating from an image
+pub fn current_theme_name(send: bool) -> Option<String> {
+    let name = read_to_string(get_cache(send).join("wal").join("theme")).ok()?;
+    Some(name.trim().to_string()).filter(|n| !n.is_empty())
+}
+
+pub fn set_current_theme_name(name: Option<&str>, send: bool) {
+    let path = get_cache(send).join("wal").join("theme");
+    match name {
+        Some(name) => {
+            let _ = write_atomic(&path, &format!("{name}\n"));
+        }
+        None => {
+            let _ = remove_file(path);
+        }
+    }
+}
+
+pub fn is_random(theme_name: &str) -> bool {
+    matches!(theme_name, "random" | "random-dark" | "random-light")
+}
+
+// resolve random, random-dark and random-light to an installed theme that
+// matches the filter and isn't the current one
+fn pick_random(theme_name: &str, filter: Option<&str>, send: bool) -> Result<String, String> {
+    let variants: &[&str] = match theme_name {
+        "random-dark" => &["dark"],
+        "random-light" => &["light"],
+        _ => &["dark", "light"],
+    };
+    let current = current_theme_name(send);
+
+    let mut themes: Vec<String> = variants
+        .iter()
+        .flat_map(|variant| collect_themes(variant, send))
+        .filter(|t| filter.is_none_or(|f| glob_match(f, t)))
+        .filter(|t| current.as_ref() != Some(t))
+        .collect();
+    themes.sort();
+    themes.dedup();
+
+    if themes.is_empty() {
+        return Err("no theme matches the filter".to_string());
+    }
+    let theme = themes.swap_remove(random_index(themes.len()));
+    info("Theme", &format!("picked {theme}"), send);
+
+    Ok(theme)
 }
 
-pub fn set_theme(theme_name: String, send: bool, scripts: bool) {
+// read an installed theme, or a random one, without exiting so the
+// follow and schedule loops survive a bad name
+pub fn load_theme(theme_name: &str, filter: Option<&str>, send: bool) -> Result<Theme, String> {
+    let theme_name = if is_random(theme_name) {
+        pick_random(theme_name, filter, send)?
+    } else {
+        theme_name.to_string()
+    };
+    find_theme(&theme_name, send)
+        .ok_or(format!("can't find theme {theme_name}"))
+        .and_then(|p| read_theme(&p))
+}
+
+// render and load an installed theme, keeping the wallpaper
+pub fn apply_theme(theme: Theme, scripts: bool, extended: bool, ttys: &[u32], send: bool) {
+    record(&Entry::new(&theme, "None", 100, extended), send);
+    render_theme(theme, scripts, extended, ttys, send);
+}
+
+// apply_theme without a history entry, for re-applying the same theme
+pub fn render_theme(theme: Theme, scripts: bool, extended: bool, ttys: &[u32], send: bool) {
+    set_current_theme_name(Some(&theme.name), send);
+    create_template(
+        (theme.colors, 100),
+        theme.special,
+        "None",
+        None,
+        extended,
+        send,
+    );
+    reload(send, true, scripts, ttys);
+}
+
+// copy the bundled colorschemes on first use
+pub fn install_themes(send: bool) {
     let base = get_config(send);
-    let mut theme: Vec<String> = ["dark", "light"]
+
+    if !theme_exists(&base) {
+        let colorschemes_dir = base.join("walrs").join("colorschemes");
+        create_dir_all(&colorschemes_dir).unwrap();
+        let walrs_cache = share_files();
+        if !theme_exists(walrs_cache.parent().unwrap()) {
+            warning("theme", "Can't find configuration directory", send);
+            exit(1)
+        }
+        run(&format!(
+            "cp -r {}/* {}",
+            walrs_cache.join("colorschemes").display(),
+            colorschemes_dir.display()
+        ));
+    }
+
+    let theme: Vec<String> = ["dark", "light"]
         .iter()
         .flat_map(|variant| collect_themes(variant, send))
         .collect();
@@ -68,28 +467,142 @@ pub fn set_theme(theme_name: String, send: bool, scripts: bool) {
             base.join("walrs").join("colorschemes").display()
         ));
     }
-    theme.sort();
-    theme.dedup();
-    if theme.contains(&theme_name) {
-        let file: Vec<String> = [
-            base.join("wal/colorschemes/dark"),
-            base.join("wal/colorschemes/light"),
-            base.join("walrs/colorschemes/dark"),
-            base.join("walrs/colorschemes/light"),
-        ]
-        .into_iter()
-        .find_map(|p| read_to_string(p.join(&theme_name)).ok())
-        .unwrap()
-        .lines()
-        .map(|l| l.to_string())
-        .collect();
+}
 
-        let rgb_colors = hex_to_rgb(file);
+pub fn set_theme(
+    theme_name: String,
+    filter: Option<String>,
+    send: bool,
+    scripts: bool,
+    extended: bool,
+    ttys: &[u32],
+) {
+    install_themes(send);
 
-        create_template((rgb_colors, 100), "None", send);
-        reload(send, true, scripts);
-    } else {
-        warning("Theme", "Can't find theme", send);
+    let theme_name = pick_pair(&theme_name);
+    if filter.is_some() && !is_random(&theme_name) {
+        warning(
+            "Theme",
+            "--filter only applies to random, random-dark and random-light",
+            send,
+        );
+    }
+    let theme = load_theme(&theme_name, filter.as_deref(), send).unwrap_or_else(|e| {
+        warning("Theme", &e, send);
+        exit(1)
+    });
+
+    apply_theme(theme, scripts, extended, ttys, send);
+}
+
+fn to_lab((r, g, b): (u8, u8, u8)) -> Lab {
+    Lab::from_color(Srgb::new(r, g, b).into_format::<f32>())
+}
+
+// mean CIEDE2000 distance from each color to its closest match in the other
+// palette, both ways, plus the background so dark and light themes don't mix
+fn palette_distance(a: &[(u8, u8, u8)], b: &[(u8, u8, u8)]) -> f32 {
+    let (a, b): (Vec<Lab>, Vec<Lab>) = (
+        a.iter().map(|c| to_lab(*c)).collect(),
+        b.iter().map(|c| to_lab(*c)).collect(),
+    );
+    let nearest = |from: &[Lab], to: &[Lab]| {
+        from.iter()
+            .map(|x| to.iter().map(|y| x.difference(*y)).fold(f32::MAX, f32::min))
+            .sum::<f32>()
+            / from.len() as f32
+    };
+
+    (nearest(&a, &b) + nearest(&b, &a)) / 2.0 + a[0].difference(b[0])
+}
+
+// rank installed themes by how close they are to the wallpaper's palette,
+// returns the best one paired with the wallpaper
+pub fn match_themes(image: &str, count: usize, send: bool) -> Entry {
+    let (colors, _, seed) = get_colors(image, send, None, None);
+
+    let mut ranked: Vec<(f32, Theme)> = ["dark", "light"]
+        .iter()
+        .flat_map(|variant| theme_paths(variant, send))
+        .filter_map(|p| read_theme(&p).ok())
+        .map(|t| (palette_distance(&colors, &t.colors), t))
+        .collect();
+    ranked.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.name.cmp(&b.1.name)));
+    ranked.dedup_by(|a, b| a.1.name == b.1.name);
+
+    if ranked.is_empty() {
+        warning("Match", "no installed themes", send);
         exit(1)
     }
+    for (distance, theme) in ranked.iter().take(count.max(1)) {
+        println!("{distance:6.2}  {:<36}{}", theme.name, swatches(theme));
+    }
+
+    let mut entry = Entry::new(&ranked.swap_remove(0).1, image, 100, false);
+    entry.seed = seed;
+
+    entry
+}
+
+// render a matched theme and set its wallpaper
+pub fn apply_match(entry: Entry, walless: bool, scripts: bool, ttys: &[u32], send: bool) {
+    info("Match", &format!("applying {}", entry.theme.name), send);
+    record(&entry, send);
+    create_template(
+        (entry.theme.colors, 100),
+        entry.theme.special,
+        &entry.wallpaper,
+        entry.seed,
+        entry.extended,
+        send,
+    );
+    set_current_theme_name(Some(&entry.theme.name), send);
+    reload(send, walless, scripts, ttys);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_hex, parse_legacy};
+    use std::path::Path;
+
+    #[test]
+    fn parses_hex_forms() {
+        assert_eq!(parse_hex("#1a2b3c"), Some((0x1a, 0x2b, 0x3c)));
+        assert_eq!(parse_hex("1a2b3c"), Some((0x1a, 0x2b, 0x3c)));
+        assert_eq!(parse_hex("#fa0"), Some((0xff, 0xaa, 0x00)));
+        assert_eq!(parse_hex("0x1A2B3C"), Some((0x1a, 0x2b, 0x3c)));
+        assert_eq!(parse_hex("0X00ff00"), Some((0x00, 0xff, 0x00)));
+    }
+
+    #[test]
+    fn parses_x11_rgb() {
+        assert_eq!(parse_hex("rgb:ff/80/00"), Some((0xff, 0x80, 0x00)));
+        assert_eq!(parse_hex("rgb:f/8/0"), Some((0xff, 0x88, 0x00)));
+        assert_eq!(parse_hex("rgb:ffff/0000/8080"), Some((0xff, 0x00, 0x80)));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        assert_eq!(parse_hex("rgb:ffffffff/0/0"), None);
+        assert_eq!(parse_hex("rgb:fffff/0/0"), None);
+        assert_eq!(parse_hex("rgb://0"), None);
+        assert_eq!(parse_hex("rgb:+f/0/0"), None);
+        assert_eq!(parse_hex("rgb:f/0/0/0"), None);
+        assert_eq!(parse_hex("rgb:f/0"), None);
+        assert_eq!(parse_hex("#12345"), None);
+        assert_eq!(parse_hex("#ggg"), None);
+        assert_eq!(parse_hex("0x"), None);
+    }
+
+    #[test]
+    fn skips_legacy_comments() {
+        let colors = "#000000 black\n".repeat(16);
+        let content = format!("#comment\n# another\n// c\n! c\n; c\n\n{colors}");
+        let parsed = parse_legacy(&content, Path::new("t")).unwrap();
+        assert_eq!(parsed, vec![(0, 0, 0); 16]);
+
+        let err = parse_legacy(&format!("nope\n{colors}"), Path::new("t")).unwrap_err();
+        assert_eq!(err, "t:1: invalid color 'nope'");
+        assert!(parse_legacy("#fff\n", Path::new("t")).is_err());
+    }
 }
@@ -0,0 +1,98 @@
+use image::DynamicImage;
+use palette::convert::FromColorUnclamped;
+use palette::{FromColor, IsWithinBounds, Oklab, Oklch, Srgb};
+
+// a single hue/chroma pair that can be sampled at any tone (0 black, 100 white)
+#[derive(Clone, Copy)]
+pub struct TonalPalette {
+    hue: f32,
+    chroma: f32,
+}
+
+impl TonalPalette {
+    pub fn tone(&self, tone: u8) -> (u8, u8, u8) {
+        let l = tone.min(100) as f32 / 100.0;
+
+        // reduce chroma until the color fits in sRGB, keeping hue and tone
+        let (mut low, mut high) = (0.0, self.chroma);
+        let fits =
+            |c: f32| Srgb::from_color_unclamped(Oklch::new(l, c, self.hue)).is_within_bounds();
+        if !fits(high) {
+            for _ in 0..16 {
+                let mid = (low + high) / 2.0;
+                if fits(mid) { low = mid } else { high = mid }
+            }
+            high = low;
+        }
+
+        let rgb = Srgb::from_color(Oklch::new(l, high, self.hue)).into_format::<u8>();
+        (rgb.red, rgb.green, rgb.blue)
+    }
+}
+
+// the five Material You key palettes built from one seed color
+pub struct TonalPalettes {
+    palettes: [(&'static str, TonalPalette); 5],
+}
+
+impl TonalPalettes {
+    pub fn new(seed: (u8, u8, u8)) -> Self {
+        let lch = Oklch::from_color(Srgb::new(seed.0, seed.1, seed.2).into_format::<f32>());
+        let hue = lch.hue.into_degrees();
+
+        // keep greyscale wallpapers grey, everything else gets a vivid primary
+        let chroma = if lch.chroma < 0.02 {
+            lch.chroma
+        } else {
+            lch.chroma.max(0.12)
+        };
+        let tonal = |hue: f32, chroma: f32| TonalPalette { hue, chroma };
+
+        Self {
+            palettes: [
+                ("primary", tonal(hue, chroma)),
+                ("secondary", tonal(hue, chroma / 3.0)),
+                ("tertiary", tonal(hue + 60.0, chroma / 2.0)),
+                ("neutral", tonal(hue, chroma.min(0.012))),
+                ("neutral_variant", tonal(hue, chroma.min(0.024))),
+            ],
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<TonalPalette> {
+        self.palettes
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, palette)| *palette)
+    }
+}
+
+// pick the seed from the hue that carries the most chroma in the wallpaper
+pub fn seed_color(image: &DynamicImage) -> Option<(u8, u8, u8)> {
+    let image = image.thumbnail(128, 128).to_rgb8();
+
+    let mut bins = [(0.0_f32, Oklab::new(0.0, 0.0, 0.0)); 36];
+    for p in image.pixels() {
+        let rgb = Srgb::new(p.0[0], p.0[1], p.0[2]).into_format::<f32>();
+        let lch = Oklch::from_color(rgb);
+        if lch.chroma < 0.04 || !(0.2..0.95).contains(&lch.l) {
+            continue;
+        }
+
+        let bin = &mut bins[(lch.hue.into_positive_degrees() / 10.0) as usize % 36];
+        let lab = Oklab::from_color(lch);
+        bin.0 += lch.chroma;
+        bin.1.l += lab.l * lch.chroma;
+        bin.1.a += lab.a * lch.chroma;
+        bin.1.b += lab.b * lch.chroma;
+    }
+
+    let (weight, sum) = bins
+        .into_iter()
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .filter(|(weight, _)| *weight > 0.0)?;
+    let lab = Oklab::new(sum.l / weight, sum.a / weight, sum.b / weight);
+    let rgb = Srgb::from_color(lab).into_format::<u8>();
+
+    Some((rgb.red, rgb.green, rgb.blue))
+}
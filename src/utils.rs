@@ -1,48 +1,161 @@
+use image::ImageFormat;
+use std::collections::HashSet;
+use std::collections::hash_map::RandomState;
 use std::env;
+use std::hash::{BuildHasher, Hasher};
 use std::path::{Path, PathBuf};
-use std::process::{exit, Stdio};
+use std::process::{Stdio, exit};
 use std::{fs, process::Command};
+use std::{io, process};
 
 pub fn share_files() -> PathBuf {
     PathBuf::from("/usr").join("share").join("walrs")
 }
 
-pub fn image_path(image: Option<String>, send: bool) -> String {
+pub fn image_path(
+    image: Option<String>,
+    order: Option<&str>,
+    recursive: bool,
+    send: bool,
+) -> String {
+    find_image(image, order, recursive, send).unwrap_or_else(|e| {
+        warning("Image", &e, send);
+        exit(1);
+    })
+}
+
+// image_path for callers that have to outlive a missing image
+pub fn find_image(
+    image: Option<String>,
+    order: Option<&str>,
+    recursive: bool,
+    send: bool,
+) -> Result<String, String> {
     match image {
         Some(ref v) if Path::new(v).exists() => match get_absolute_path(v) {
-            Some(p) => {
-                if Path::new(&p).is_file() {
-                    p
-                } else {
-                    std::str::from_utf8(
-                        &std::process::Command::new("sh")
-                            .arg("-c")
-                            .arg(format!("find \"{p}\" -type f | sort -R | head -n1"))
-                            .output()
-                            .unwrap()
-                            .stdout,
-                    )
-                    .unwrap()
-                    .trim()
-                    .to_string()
-                }
-            }
-            None => {
-                warning("Wallpaper", "Can't find wallpaper absolute path!", send);
-                exit(1);
-            }
+            Some(p) if Path::new(&p).is_file() => Ok(p),
+            Some(p) => pick_wallpaper(Path::new(&p), order.unwrap_or("random"), recursive, send),
+            None => Err("Can't find wallpaper absolute path!".to_string()),
         },
-        Some(_) => {
-            warning("Image", "Image does not exist", send);
-            exit(1);
+        Some(_) => Err("Image does not exist".to_string()),
+        None => Err("Can't find Image".to_string()),
+    }
+}
+
+// images in `dir`, skipping hidden files and folders; with `recursive` the
+// subfolders too, following symlinks but never scanning a folder twice so a
+// link back up can't loop forever
+fn scan_images(
+    dir: &Path,
+    recursive: bool,
+    seen: &mut HashSet<PathBuf>,
+    images: &mut Vec<PathBuf>,
+) {
+    if !fs::canonicalize(dir).is_ok_and(|d| seen.insert(d)) {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+        {
+            continue;
         }
-        None => {
-            warning("Image", "Can't find Image", send);
-            exit(1);
+        if path.is_dir() {
+            if recursive {
+                scan_images(&path, recursive, seen, images);
+            }
+        } else if path.is_file()
+            && path
+                .extension()
+                .and_then(ImageFormat::from_extension)
+                .is_some_and(|f| f.reading_enabled())
+        {
+            images.push(path);
         }
     }
 }
 
+// name starts from the first image by name and mtime from the newest one,
+// next and previous step through the folder by name from the last pick, which
+// is kept in ~/.cache/walrs/positions; no order picks the wallpaper that is
+// already set unless it is the only image
+fn pick_wallpaper(dir: &Path, order: &str, recursive: bool, send: bool) -> Result<String, String> {
+    let mut images = Vec::new();
+    scan_images(dir, recursive, &mut HashSet::new(), &mut images);
+    if images.is_empty() {
+        return Err("No images in this directory".to_string());
+    }
+
+    if order == "mtime" {
+        images.sort_by_key(|p| std::cmp::Reverse(fs::metadata(p).and_then(|m| m.modified()).ok()));
+    } else {
+        images.sort();
+    }
+
+    let current = fs::read_to_string(get_cache(send).join("wal").join("wal"))
+        .ok()
+        .and_then(|w| w.lines().next().map(PathBuf::from));
+    let positions_file = get_cache(send).join("walrs").join("positions");
+    let positions = fs::read_to_string(&positions_file).unwrap_or_default();
+    let key = dir.display().to_string();
+    let last = positions
+        .lines()
+        .find_map(|l| l.strip_prefix(&format!("{key}\t")))
+        .map(PathBuf::from)
+        .or(current.clone());
+
+    let len = images.len();
+    let at = last.and_then(|l| images.iter().position(|p| *p == l));
+    let forward = |i: usize| (i + 1) % len;
+    let backward = |i: usize| (i + len - 1) % len;
+    let (picked, step): (usize, &dyn Fn(usize) -> usize) = match order {
+        "name" | "mtime" => (0, &forward),
+        "next" => (at.map_or(0, forward), &forward),
+        "previous" => (at.map_or(len - 1, backward), &backward),
+        "random" => {
+            let candidates: Vec<usize> = (0..len)
+                .filter(|i| len == 1 || Some(&images[*i]) != current.as_ref())
+                .collect();
+            (candidates[random_index(candidates.len())], &forward)
+        }
+        _ => {
+            return Err(format!(
+                "unknown order {order} (random, name, mtime, next, previous)"
+            ));
+        }
+    };
+    let picked = if len > 1 && Some(&images[picked]) == current.as_ref() {
+        step(picked)
+    } else {
+        picked
+    };
+    let picked = images[picked].display().to_string();
+    remember_position(dir, &picked, send);
+
+    Ok(picked)
+}
+
+// keep `image` as the last pick of `dir` for the ordered modes
+pub fn remember_position(dir: &Path, image: &str, send: bool) {
+    let positions_file = get_cache(send).join("walrs").join("positions");
+    let key = dir.display().to_string();
+    let mut lines: Vec<String> = fs::read_to_string(&positions_file)
+        .unwrap_or_default()
+        .lines()
+        .filter(|l| !l.starts_with(&format!("{key}\t")))
+        .map(|l| l.to_string())
+        .collect();
+    lines.push(format!("{key}\t{image}"));
+    if fs::create_dir_all(get_cache(send).join("walrs")).is_ok() {
+        let _ = write_atomic(&positions_file, &(lines.join("\n") + "\n"));
+    }
+}
+
 pub fn run(command: &str) -> bool {
     Command::new("sh")
         .arg("-c")
@@ -54,17 +167,28 @@ pub fn run(command: &str) -> bool {
         .unwrap_or(false)
 }
 
+// stdout of a successful command, None if it fails or isn't installed
+pub fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
 pub fn print_colors(send: bool) {
-    if send {
-        if let Ok(output) = Command::new("bash")
+    if send
+        && let Ok(output) = Command::new("bash")
             .arg("-c")
             .arg(r#"for i in {30..37} 90; do echo -en "\033[0;${i}m●\033[0m "; done; echo"#)
             .output()
-        {
-            if output.status.success() {
-                print!("{}", String::from_utf8_lossy(&output.stdout));
-            }
-        }
+        && output.status.success()
+    {
+        print!("{}", String::from_utf8_lossy(&output.stdout));
     }
 }
 
@@ -106,3 +230,116 @@ pub fn get_absolute_path(path_str: &str) -> Option<String> {
         Some(path_str.to_string())
     }
 }
+
+// write through a temp file + rename so readers never see a partial file,
+// returns false when the file already had this content
+pub fn write_atomic(path: &Path, content: &str) -> io::Result<bool> {
+    let old = fs::metadata(path).ok();
+    if old.is_some() && fs::read(path).is_ok_and(|v| v == content.as_bytes()) {
+        return Ok(false);
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{name}.{}.tmp", process::id()));
+    fs::write(&tmp, content)?;
+    if let Some(meta) = old {
+        let _ = fs::set_permissions(&tmp, meta.permissions());
+    }
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })?;
+
+    Ok(true)
+}
+
+// random number in 0..len without pulling in a rng crate
+pub fn random_index(len: usize) -> usize {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default(),
+    );
+    (hasher.finish() % len.max(1) as u64) as usize
+}
+
+// a theme or snapshot name that can't point outside its folder
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+// shell-style glob with `*` and `?`
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_match, scan_images, valid_name};
+    use std::collections::HashSet;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_match("base16-*", "base16-gruvbox-dark"));
+        assert!(glob_match("*gruvbox*", "base16-gruvbox-dark"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("base16-*", "gruvbox"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn checks_names() {
+        assert!(valid_name("gruvbox-dark"));
+        assert!(valid_name("my theme.v2"));
+        for bad in ["", "a/b", "../../x", "..", "a\\b", "x..y"] {
+            assert!(!valid_name(bad), "{bad}");
+        }
+    }
+
+    #[test]
+    fn scans_images() {
+        let dir = std::env::temp_dir().join(format!("walrs-scan-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for file in ["a.png", "notes.txt", ".hidden.png", "sub/b.jpg"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        // a link back up must not loop
+        symlink(&dir, dir.join("sub").join("up")).unwrap();
+
+        let scan = |recursive| {
+            let mut images = Vec::new();
+            scan_images(&dir, recursive, &mut HashSet::new(), &mut images);
+            images.sort();
+            images
+        };
+        assert_eq!(scan(false), vec![dir.join("a.png")]);
+        assert_eq!(scan(true), vec![dir.join("a.png"), dir.join("sub/b.jpg")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,9 @@
+use crate::reload::get_wallpaper;
 use crate::utils::run;
-use crate::utils::{get_absolute_path, info, warning};
+use crate::utils::{get_absolute_path, get_cache, image_path, info, warning, write_atomic};
 use std::env;
 use std::path::Path;
-use std::process::{exit, Command, Stdio};
+use std::process::{Command, Stdio, exit};
 
 fn run_with_output(command: &str) -> Option<String> {
     let output = Command::new("sh")
@@ -67,23 +68,23 @@ fn get_desktop_env() -> Option<String> {
     ];
 
     for key in keys.iter() {
-        if let Ok(val) = env::var(key) {
-            if !val.is_empty() {
-                if *key == "DESKTOP_STARTUP_ID" && val.contains("awesome") {
-                    return Some("AWESOME".to_string());
-                }
+        if let Ok(val) = env::var(key)
+            && !val.is_empty()
+        {
+            if *key == "DESKTOP_STARTUP_ID" && val.contains("awesome") {
+                return Some("AWESOME".to_string());
+            }
 
-                if *key == "WAYLAND_DISPLAY" {
-                    // Try to identify specific Wayland compositor
-                    if let Ok(desktop) = env::var("XDG_CURRENT_DESKTOP") {
-                        return Some(desktop);
-                    } else {
-                        return Some("WAYLAND".to_string());
-                    }
+            if *key == "WAYLAND_DISPLAY" {
+                // Try to identify specific Wayland compositor
+                if let Ok(desktop) = env::var("XDG_CURRENT_DESKTOP") {
+                    return Some(desktop);
+                } else {
+                    return Some("WAYLAND".to_string());
                 }
-
-                return Some(val);
             }
+
+            return Some(val);
         }
     }
     None
@@ -389,3 +390,17 @@ pub fn change_wallpaper(img: &str, send: bool) {
         }
     }
 }
+
+// set the wallpaper without touching the colors, or print the current one
+pub fn set_wallpaper(image: Option<String>, order: Option<&str>, recursive: bool, send: bool) {
+    let cache = get_cache(send).join("wal");
+    let Some(image) = image else {
+        println!("{}", get_wallpaper(&cache, send));
+        return;
+    };
+
+    let image = image_path(Some(image), order, recursive, send);
+    change_wallpaper(&image, send);
+    // remember it so reload and folder ordering pick it up
+    let _ = write_atomic(&cache.join("wal"), &format!("{image}\n"));
+}
@@ -0,0 +1,146 @@
+use crate::create_templates::render_template;
+use crate::reload::reload;
+use crate::theme::{current_theme_name, load_theme, render_theme, theme_name};
+use crate::utils::{get_cache, get_config, info, warning};
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::BTreeSet;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::exit;
+use std::thread::sleep;
+use std::time::Duration;
+
+// editors save through swap and backup files, skip those
+fn is_scratch(name: &str) -> bool {
+    name.starts_with('.') || name.ends_with('~') || name.ends_with(".swp") || name.ends_with(".tmp")
+}
+
+// re-render templates as they are saved, re-apply the current theme when its
+// file is edited and reload terminals whenever ~/.cache/wal/colors changes
+pub fn watch(scripts: bool, ttys: &[u32], send: bool) {
+    let mut inotify = Inotify::init().unwrap_or_else(|e| {
+        warning("Watch", &format!("can't start inotify: {e}"), send);
+        exit(1)
+    });
+    let mask =
+        WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::MOVED_FROM | WatchMask::DELETE;
+    let config = get_config(send);
+    let cache = get_cache(send).join("wal");
+
+    let add = |path: &Path| {
+        inotify
+            .watches()
+            .add(path, mask)
+            .inspect_err(|_| warning("Watch", &format!("can't watch {}", path.display()), send))
+            .ok()
+    };
+    let templates = add(&config.join("walrs").join("templates"));
+    let themes: Vec<WatchDescriptor> = ["wal", "walrs"]
+        .iter()
+        .flat_map(|folder| {
+            ["dark", "light"].map(|v| config.join(folder).join("colorschemes").join(v))
+        })
+        .filter(|p| p.exists())
+        .filter_map(|p| add(&p))
+        .collect();
+    let colors = add(&cache);
+    if templates.is_none() && themes.is_empty() && colors.is_none() {
+        exit(1)
+    }
+    info("Watch", "watching templates, themes and colors", send);
+
+    // what the terminals were last loaded with, so rewriting the same colors
+    // doesn't reload them again
+    let mut loaded = read_to_string(cache.join("colors")).unwrap_or_default();
+
+    let mut buffer = [0; 4096];
+    loop {
+        // one action per file, even when an editor writes it several times
+        let mut changed_templates = BTreeSet::new();
+        let mut changed_themes = BTreeSet::new();
+        let mut colors_changed = false;
+        let mut blocking = true;
+
+        // keep reading until things settle, editors save through a temp
+        // file that is renamed right after it is written
+        loop {
+            let events = if blocking {
+                inotify.read_events_blocking(&mut buffer)
+            } else {
+                sleep(Duration::from_millis(100));
+                inotify.read_events(&mut buffer)
+            };
+            let events = match events {
+                Ok(events) => events,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    warning("Watch", "can't read events", send);
+                    exit(1)
+                }
+            };
+            blocking = false;
+
+            for event in events {
+                let Some(name) = event.name.and_then(|n| n.to_str()) else {
+                    continue;
+                };
+                if is_scratch(name) {
+                    continue;
+                }
+                if Some(&event.wd) == templates.as_ref() {
+                    if event
+                        .mask
+                        .intersects(EventMask::MOVED_FROM | EventMask::DELETE)
+                    {
+                        changed_templates.remove(name);
+                    } else {
+                        changed_templates.insert(name.to_string());
+                    }
+                } else if themes.contains(&event.wd) {
+                    changed_themes.extend(theme_name(Path::new(name)));
+                } else if Some(&event.wd) == colors.as_ref() {
+                    // reload writes the sequences after the colors, so a walrs
+                    // command that reloaded by itself is left alone
+                    match name {
+                        "colors" => colors_changed = true,
+                        "sequences" => {
+                            colors_changed = false;
+                            loaded = read_to_string(cache.join("colors")).unwrap_or_default();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        for name in changed_templates {
+            if render_template(&name, send) {
+                info("Watch", &format!("rendered {name}"), send);
+                colors_changed |= name == "colors";
+            }
+        }
+
+        // the same theme again, so no new history entry for every save
+        let current = current_theme_name(send);
+        if let Some(name) = current.filter(|t| changed_themes.contains(t)) {
+            match load_theme(&name, None, send) {
+                Ok(theme) => {
+                    info("Watch", &format!("{name} changed, applying it again"), send);
+                    let extended = cache.join("colors-256").exists();
+                    render_theme(theme, scripts, extended, ttys, send);
+                    loaded = read_to_string(cache.join("colors")).unwrap_or_default();
+                    colors_changed = false;
+                }
+                Err(e) => warning("Watch", &e, send),
+            }
+        }
+
+        let now = read_to_string(cache.join("colors")).unwrap_or_default();
+        if colors_changed && now != loaded {
+            info("Watch", "colors changed, reloading", send);
+            reload(send, true, scripts, ttys);
+            loaded = now;
+        }
+    }
+}